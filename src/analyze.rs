@@ -0,0 +1,92 @@
+use std::fs::File;
+use std::io::Read;
+use std::sync::Arc;
+
+use serde_json;
+
+use bot::Bot;
+use config::AnalyzeConfig;
+use search;
+use state::State;
+use weights::Weights;
+use evaluator::Model;
+
+/// Reads a state in the format the server sends.
+fn load_state(path: &str) -> Result<State, String> {
+    let mut text = String::new();
+    File::open(path)
+        .and_then(|mut f| f.read_to_string(&mut text))
+        .map_err(|e| format!("Could not read {}: {}", path, e))?;
+
+    let mut s: State = serde_json::from_str(&text).map_err(|e| format!("{}: {}", path, e))?;
+    s.game.board.initialize();
+
+    Ok(s)
+}
+
+/// Searches a saved position and prints what Beagle would play and why.
+pub fn run(config: &AnalyzeConfig) -> Result<(), String> {
+    let mut s = load_state(&config.path)?;
+    if s.game.finished {
+        return Err(format!("{}: the game is already finished", config.path));
+    }
+
+    // Analyze for whoever is to move.
+    s.hero = s.game.heroes[s.game.turn % 4].clone();
+
+    let mut bot = Bot::new();
+    bot.set_verbose(false);
+    bot.set_move_time(config.move_time);
+    bot.set_threads(config.threads);
+    bot.set_strategy(search::from_name(&config.search).unwrap());
+    bot.set_quiescence_plies(config.quiescence);
+    if let Some(ref path) = config.weights {
+        bot.set_weights(Weights::load(path)?);
+    }
+    if let Some(ref path) = config.model {
+        bot.set_evaluator(Arc::new(Model::load(path)?));
+    }
+    if let Some(depth) = config.depth {
+        bot.set_max_depth(depth);
+    }
+
+    println!("{}", s.game.board);
+    println!("Turn {}/{}, @{} {} to move (life {}, gold {}, mines {})\n",
+             s.game.turn,
+             s.game.max_turns,
+             s.hero.id,
+             s.hero.name,
+             s.hero.life,
+             s.hero.gold,
+             s.hero.mine_count);
+
+    let dir = bot.choose_move(&mut s);
+    let info = bot.last_search().clone();
+
+    println!("Move: {}", dir);
+    println!("Depth {}, score {} ({} evaluation), {} nodes in {} ms",
+             info.depth,
+             info.score,
+             bot.evaluator_name(),
+             info.nodes,
+             info.time_ms);
+    println!("{} root searches, {} re-searches, {} quiescence nodes, {} reductions, {} \
+              extensions",
+             info.stats.root_searches,
+             info.stats.researches,
+             info.stats.quiescence_nodes,
+             info.stats.reductions,
+             info.stats.extensions);
+    println!("{} null moves, {} null move cutoffs",
+             info.stats.null_moves,
+             info.stats.null_cutoffs);
+
+    println!("PV: {}\n", info.pv.join(", "));
+
+    println!("move     score    nodes");
+    for rm in bot.root_moves(&mut s, info.depth) {
+        println!("{:<6} {:>7} {:>8}", format!("{}", rm.dir), rm.score, rm.nodes);
+    }
+
+    Ok(())
+}
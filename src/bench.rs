@@ -0,0 +1,122 @@
+use std::sync::Arc;
+
+use time;
+
+use bot::Bot;
+use config::BenchConfig;
+use mapgen;
+use policy;
+use search;
+use state::State;
+use weights::Weights;
+use evaluator::Model;
+use zobrist;
+
+/// Seeds of the generated maps the positions are taken from.
+const SEEDS: [u64; 6] = [1, 2, 3, 4, 5, 6];
+/// Game turns at which positions are taken, after greedy play from the start.
+const TURNS: [usize; 3] = [0, 60, 200];
+/// Seed of the Zobrist keys, which decide the transposition table's
+/// collisions and so the node counts.
+const ZOBRIST_SEED: u64 = 1;
+
+/// The benchmark positions: the same ones on every run, so node counts can
+/// be compared between versions.
+fn positions() -> Vec<(u64, State)> {
+    let names = [String::from("a"), String::from("b"), String::from("c"), String::from("d")];
+    let mut result = Vec::new();
+
+    for &seed in &SEEDS {
+        let board = mapgen::generate(mapgen::size_for_seed(seed), seed);
+        let mut s = State::new(&format!("bench-{}", seed), board, 1200, &names);
+
+        for &turn in &TURNS {
+            while s.game.turn < turn {
+                let idx = s.game.turn % 4;
+                s.hero = s.game.heroes[idx].clone();
+                let dir = policy::greedy_move(&mut s);
+                s.make_move(dir);
+            }
+
+            s.hero = s.game.heroes[s.game.turn % 4].clone();
+            result.push((seed, s.clone()));
+        }
+    }
+
+    result
+}
+
+/// Searches every benchmark position to a fixed depth and prints the nodes
+/// and time it took, to measure changes to move ordering and pruning. With a
+/// time limit, prints how deep the search got instead.
+pub fn run(config: &BenchConfig) -> Result<(), String> {
+    unsafe {
+        zobrist::ZOBRIST = zobrist::ZobristTable::seeded(ZOBRIST_SEED);
+    }
+
+    let mut bot = Bot::with_table_size(1000000);
+    bot.set_verbose(false);
+    match config.move_time {
+        Some(ms) => bot.set_move_time(ms),
+        None => {
+            bot.set_max_depth(config.depth);
+            // A day is as good as no limit.
+            bot.set_move_time(86400000);
+        }
+    }
+    bot.set_strategy(search::from_name(&config.search).unwrap());
+    bot.set_quiescence_plies(config.quiescence);
+    bot.set_move_ordering(config.ordering);
+    bot.set_reductions(config.reductions);
+    bot.set_extensions(config.extensions);
+    bot.set_null_moves(config.null_moves);
+    if let Some(ref path) = config.weights {
+        bot.set_weights(Weights::load(path)?);
+    }
+    if let Some(ref path) = config.model {
+        bot.set_evaluator(Arc::new(Model::load(path)?));
+    }
+
+    let positions = positions();
+    let mut total_nodes = 0u64;
+    let mut total_depth = 0usize;
+    let start = time::get_time();
+
+    println!("seed  turn  move        score  depth       nodes      ms");
+    for &(seed, ref s) in &positions {
+        let mut s = s.clone();
+        bot.new_game();
+        let dir = bot.choose_move(&mut s);
+        let info = bot.last_search().clone();
+        total_nodes += info.nodes;
+        total_depth += info.depth as usize;
+
+        println!("{:>4}  {:>4}  {:<8}  {:>7}  {:>5}  {:>10}  {:>6}",
+                 seed,
+                 s.game.turn,
+                 dir.to_string(),
+                 info.score,
+                 info.depth,
+                 info.nodes,
+                 info.time_ms);
+    }
+
+    let ms = (time::get_time() - start).num_milliseconds();
+    match config.move_time {
+        Some(move_time) => {
+            println!("\n{} ms per position: average depth {:.2}, {} nodes",
+                     move_time,
+                     total_depth as f64 / positions.len() as f64,
+                     total_nodes)
+        }
+        None => {
+            println!("\nDepth {}: {} nodes in {} ms ({} nodes/s)",
+                     config.depth,
+                     total_nodes,
+                     ms,
+                     total_nodes * 1000 / ms.max(1) as u64)
+        }
+    }
+
+    Ok(())
+}
@@ -5,6 +5,7 @@ use std::collections::VecDeque;
 use std::cmp;
 
 use fnv::FnvHasher;
+use serde::ser::{Serialize, Serializer, SerializeStruct};
 
 use tile::Tile;
 use position::Position;
@@ -54,6 +55,15 @@ impl PartialEq for Board {
     }
 }
 
+impl Serialize for Board {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut st = serializer.serialize_struct("Board", 2)?;
+        st.serialize_field("size", &self.size)?;
+        st.serialize_field("tiles", &self.tiles())?;
+        st.end()
+    }
+}
+
 impl fmt::Display for Board {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         if self.initialized {
@@ -75,6 +85,93 @@ impl fmt::Display for Board {
 }
 
 impl Board {
+    pub fn from_tiles(size: i8, tiles: String) -> Board {
+        let mut b = Board {
+            size: size,
+            board: Vec::new(),
+            initialized: false,
+            tiles: tiles,
+            mine_pos: Vec::new(),
+            tavern_pos: Vec::new(),
+            hash: 0,
+            pathcache: Vec::new(),
+            minecache: Vec::new(),
+            max_dist: 0,
+        };
+        b.initialize();
+        b
+    }
+
+    /// Parses a map in the format the `Display` impl prints: one row per
+    /// line, two characters per tile, with heroes @1 to @4 on their spawns.
+    pub fn parse_map(text: &str) -> Result<Board, String> {
+        // Rows of air are all blanks, so only drop lines that are truly empty.
+        let rows: Vec<&str> = text.lines().filter(|l| !l.is_empty()).collect();
+        let size = rows.len();
+
+        if size < 4 || size > 35 {
+            return Err(format!("Invalid map size: {}", size));
+        }
+
+        let mut tiles = String::with_capacity(2 * size * size);
+        let mut heroes = [0; 4];
+
+        for (i, row) in rows.iter().enumerate() {
+            if row.len() != 2 * size {
+                return Err(format!("Row {} has {} characters, expected {}",
+                                   i + 1,
+                                   row.len(),
+                                   2 * size));
+            }
+
+            for pair in row.as_bytes().chunks(2) {
+                match (pair[0], pair[1]) {
+                    (b'#', b'#') | (b' ', b' ') | (b'[', b']') => (),
+                    (b'$', b'-') | (b'$', b'1'..=b'4') => (),
+                    (b'@', h @ b'1'..=b'4') => heroes[(h - b'1') as usize] += 1,
+                    _ => return Err(format!("Invalid tile in row {}: {}", i + 1, row)),
+                }
+            }
+
+            tiles.push_str(row);
+        }
+
+        if heroes != [1, 1, 1, 1] {
+            return Err(String::from("Map must contain each of the heroes @1 to @4 exactly once"));
+        }
+
+        Ok(Board::from_tiles(size as i8, tiles))
+    }
+
+    /// Renders the board in the server's `tiles` format.
+    pub fn tiles(&self) -> String {
+        if !self.initialized {
+            return self.tiles.clone();
+        }
+
+        let mut result = String::with_capacity(2 * self.board.len());
+        for t in &self.board {
+            result.push_str(&format!("{}", t));
+        }
+        result
+    }
+
+    /// Returns the positions of heroes 1 to 4.
+    pub fn hero_positions(&self) -> [Position; 4] {
+        let mut result = [Position { x: -1, y: -1 }; 4];
+
+        for x in 0..self.size {
+            for y in 0..self.size {
+                let pos = Position { x: x, y: y };
+                if let Tile::Hero(id) = self.tile_at(&pos) {
+                    result[id - 1] = pos;
+                }
+            }
+        }
+
+        result
+    }
+
     pub fn initialize(&mut self) {
         self.board = vec![Tile::Wall; (self.size as usize * self.size as usize)];
         self.mine_pos = vec![];
@@ -1,4 +1,9 @@
+use std::collections::HashSet;
 use std::hash::{Hash, Hasher};
+use std::mem;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
 use fnv::FnvHasher;
 
 use time;
@@ -7,35 +12,328 @@ use state::State;
 use direction::Direction;
 use mv::Move;
 use position::Position;
+use tile::Tile;
 use transposition_table::{Table, Entry};
-use lru::LRU;
+use ordering::{CounterMoves, History, Killers, MoveOrdering};
+use search::{Mtdf, SearchStrategy, Window};
+use evaluator::{Evaluator, Handcrafted};
+use weights::Weights;
+
+/// What the last call to choose_move found.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SearchInfo {
+    pub depth: u8,
+    pub score: i32,
+    pub nodes: u64,
+    pub time_ms: i64,
+    pub best: Direction,
+    #[serde(default)]
+    pub pv: Vec<String>,
+    #[serde(default)]
+    pub stats: SearchStats,
+}
+
+/// How often the search had to look at the same thing twice.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct SearchStats {
+    /// Searches of the root, more than one per iteration for MTD(f) and
+    /// failed aspiration windows.
+    pub root_searches: u64,
+    /// Moves searched again after failing a null window search.
+    pub researches: u64,
+    /// Nodes searched beyond the nominal depth because of fights.
+    #[serde(default)]
+    pub quiescence_nodes: u64,
+    /// Late moves searched to less than the full depth.
+    #[serde(default)]
+    pub reductions: u64,
+    /// Moves searched a ply deeper because they put our hero in danger.
+    #[serde(default)]
+    pub extensions: u64,
+    /// Searches of MIN nodes in which the opponents pass.
+    #[serde(default)]
+    pub null_moves: u64,
+    /// MIN nodes cut off because passing was already good enough for the
+    /// opponents.
+    #[serde(default)]
+    pub null_cutoffs: u64,
+}
+
+/// Moves searched at full depth before late move reductions start.
+const LMR_FULL_MOVES: usize = 3;
+/// Moves after which late moves are reduced by two plies instead of one.
+const LMR_LATE_MOVES: usize = 8;
+/// Least remaining depth at which moves are reduced.
+const LMR_MIN_DEPTH: u8 = 3;
+/// Most danger extensions on the way from the root to a node.
+const MAX_EXTENSIONS: u8 = 1;
+/// Life at or below which our hero is in danger of being killed.
+const DANGER_LIFE: u8 = 25;
+/// Plies a null move search is shallower than the node it is made at.
+const NULL_REDUCTION: u8 = 3;
+/// Least remaining depth at which the opponents may pass.
+const NULL_MIN_DEPTH: u8 = 4;
+
+/// The key of `s` in the transposition table.
+pub fn position_hash(s: &State) -> u64 {
+    let mut sh = FnvHasher::default();
+    s.hash(&mut sh);
+    sh.finish()
+}
+
+/// The depths to search a move to, as decided by child_depth.
+#[derive(Clone, Copy)]
+struct ChildDepth {
+    /// The depth the move is searched to in the end.
+    full: u8,
+    /// The depth to try first, less than `full` for a reduced move.
+    reduced: u8,
+    /// Whether `full` includes an extension.
+    extended: bool,
+}
+
+/// The score of one root move, searched with a full window.
+#[derive(Clone, Debug)]
+pub struct RootMove {
+    pub dir: Direction,
+    pub score: i32,
+    pub nodes: u64,
+}
 
 pub struct Bot {
     initialized: bool,
     threat_list: [u8; 4],
-    max_history: LRU<(Position, Direction)>,
-    min_history: LRU<(u8, Position, Direction)>,
-    tt: Table,
+    history: History,
+    killers: Killers,
+    counter_moves: CounterMoves,
+    /// The moves from the root to the node being searched.
+    move_stack: Vec<Move>,
+    ordering: MoveOrdering,
+    tt: Arc<Table>,
     elo_cache: [[[f64; 3]; 4]; 4],
+    move_time: i64,
+    max_depth: u8,
+    verbose: bool,
+    last_search: SearchInfo,
+    stop: Arc<AtomicBool>,
+    helpers: Vec<Bot>,
+    strategy: Arc<dyn SearchStrategy>,
+    pub stats: SearchStats,
+    quiescence_plies: u8,
+    reductions: bool,
+    extensions: bool,
+    /// Danger extensions on the way from the root to the node being
+    /// searched.
+    path_extensions: u8,
+    null_moves: bool,
+    weights: Weights,
+    evaluator: Arc<dyn Evaluator>,
 }
 
 impl Bot {
     pub fn new() -> Bot {
+        Bot::with_table_size(10000000u64)
+    }
+
+    pub fn with_table_size(num_entries: u64) -> Bot {
+        Bot::with_table(Arc::new(Table::new(num_entries)))
+    }
+
+    fn with_table(tt: Arc<Table>) -> Bot {
         Bot {
             initialized: false,
             threat_list: [1, 2, 3, 0],
-            tt: Table::new(10000000u64),
-            max_history: LRU::<(Position, Direction)>::new((Position { x: -1, y: -1 },
-                                                            Direction::Stay)),
-            min_history: LRU::<(u8, Position, Direction)>::new((4,
-                                                                Position { x: -1, y: -1 },
-                                                                Direction::Stay)),
-
+            tt: tt,
+            history: History::new(),
+            killers: Killers::new(),
+            counter_moves: CounterMoves::new(),
+            move_stack: Vec::new(),
+            ordering: MoveOrdering::default(),
             elo_cache: [[[0f64; 3]; 4]; 4],
+            move_time: 800,
+            max_depth: 32,
+            verbose: true,
+            last_search: SearchInfo::default(),
+            stop: Arc::new(AtomicBool::new(false)),
+            helpers: Vec::new(),
+            strategy: Arc::new(Mtdf),
+            stats: SearchStats::default(),
+            quiescence_plies: 4,
+            reductions: true,
+            extensions: true,
+            path_extensions: 0,
+            null_moves: false,
+            weights: Weights::default(),
+            evaluator: Arc::new(Handcrafted),
+        }
+    }
+
+    /// Searches with `threads` threads in total. The helpers run the same
+    /// search as the main thread and only share what they find through the
+    /// transposition table (Lazy SMP).
+    pub fn set_threads(&mut self, threads: usize) {
+        self.helpers = (1..threads)
+            .map(|_| {
+                let mut h = Bot::with_table(self.tt.clone());
+                h.stop = self.stop.clone();
+                h.verbose = false;
+                h.strategy = self.strategy.clone();
+                h.quiescence_plies = self.quiescence_plies;
+                h.ordering = self.ordering;
+                h.reductions = self.reductions;
+                h.extensions = self.extensions;
+                h.null_moves = self.null_moves;
+                h.weights = self.weights.clone();
+                h.evaluator = self.evaluator.clone();
+                h
+            })
+            .collect();
+    }
+
+    pub fn set_move_time(&mut self, milliseconds: i64) {
+        self.move_time = milliseconds;
+    }
+
+    pub fn set_max_depth(&mut self, depth: u8) {
+        self.max_depth = depth;
+    }
+
+    /// Limits how many plies the search goes on past its depth while heroes
+    /// are fighting. 0 turns quiescence search off.
+    pub fn set_quiescence_plies(&mut self, plies: u8) {
+        self.quiescence_plies = plies;
+        for h in &mut self.helpers {
+            h.quiescence_plies = plies;
+        }
+    }
+
+    /// Turns move ordering heuristics on or off, to measure what they are
+    /// worth.
+    pub fn set_move_ordering(&mut self, ordering: MoveOrdering) {
+        self.ordering = ordering;
+        for h in &mut self.helpers {
+            h.ordering = ordering;
         }
     }
 
-    fn eval(&mut self, s: &mut State) -> i32 {
+    /// Turns late move reductions on or off.
+    pub fn set_reductions(&mut self, reductions: bool) {
+        self.reductions = reductions;
+        for h in &mut self.helpers {
+            h.reductions = reductions;
+        }
+    }
+
+    /// Turns the extension of moves that put our hero in danger on or off.
+    pub fn set_extensions(&mut self, extensions: bool) {
+        self.extensions = extensions;
+        for h in &mut self.helpers {
+            h.extensions = extensions;
+        }
+    }
+
+    /// Turns null move pruning at MIN nodes on or off. It is off by default:
+    /// it gains about a ply, but the shallower searches miss mines just
+    /// beyond their horizon, and in self-play it hasn't shown a clear gain.
+    pub fn set_null_moves(&mut self, null_moves: bool) {
+        self.null_moves = null_moves;
+        for h in &mut self.helpers {
+            h.null_moves = null_moves;
+        }
+    }
+
+    /// Evaluates with `weights` from now on.
+    pub fn set_weights(&mut self, weights: Weights) {
+        // Scores in the table and the rating changes were computed with the
+        // old weights.
+        self.tt.clear();
+        self.initialized = false;
+
+        for h in &mut self.helpers {
+            h.weights = weights.clone();
+            h.initialized = false;
+        }
+        self.weights = weights;
+    }
+
+    /// Evaluates with `evaluator` from now on. The handcrafted evaluation
+    /// is the default.
+    pub fn set_evaluator(&mut self, evaluator: Arc<dyn Evaluator>) {
+        self.tt.clear();
+
+        for h in &mut self.helpers {
+            h.evaluator = evaluator.clone();
+        }
+        self.evaluator = evaluator;
+    }
+
+    pub fn evaluator_name(&self) -> &'static str {
+        self.evaluator.name()
+    }
+
+    pub fn set_strategy(&mut self, strategy: Arc<dyn SearchStrategy>) {
+        // Strategies don't agree on what the table entries mean.
+        self.tt.clear();
+
+        for h in &mut self.helpers {
+            h.strategy = strategy.clone();
+        }
+        self.strategy = strategy;
+    }
+
+    pub fn strategy_name(&self) -> &'static str {
+        self.strategy.name()
+    }
+
+    pub fn table(&self) -> &Table {
+        &self.tt
+    }
+
+    /// Whether a search that has visited `nodes` nodes should give up. Only
+    /// looks at the clock every 1024 nodes.
+    pub fn out_of_time(&self, nodes: u64, end_time: time::Timespec) -> bool {
+        (nodes < 10u64 || nodes & 1023u64 == 1023u64) &&
+        (time::get_time() > end_time || self.stop.load(Ordering::Relaxed))
+    }
+
+    pub fn set_verbose(&mut self, verbose: bool) {
+        self.verbose = verbose;
+    }
+
+    pub fn last_search(&self) -> &SearchInfo {
+        &self.last_search
+    }
+
+    /// A flag that makes a running search give up when set. It is cleared
+    /// by every call to choose_move.
+    pub fn stop_flag(&self) -> Arc<AtomicBool> {
+        self.stop.clone()
+    }
+
+    pub fn new_game(&mut self) {
+        self.initialized = false;
+        self.threat_list = [1, 2, 3, 0];
+        self.elo_cache = [[[0f64; 3]; 4]; 4];
+        self.tt.clear();
+        self.history.clear();
+        self.counter_moves.clear();
+
+        for h in &mut self.helpers {
+            h.initialized = false;
+            h.threat_list = [1, 2, 3, 0];
+            h.history.clear();
+            h.counter_moves.clear();
+        }
+    }
+
+    pub fn eval(&mut self, s: &mut State) -> i32 {
+        let bot: &Bot = self;
+        bot.evaluator.eval(bot, s)
+    }
+
+    /// The evaluation of `Handcrafted`.
+    pub fn handcrafted_eval(&self, s: &mut State) -> i32 {
+        let w = &self.weights;
         let turns_left = (s.game.max_turns - s.game.turn) / 4;
         let mut pred_score = [0f64, 0f64, 0f64, 0f64, 0f64];
         let mut rank_adj = [0f64, 0f64, 0f64, 0f64, 0f64];
@@ -44,24 +342,26 @@ impl Bot {
 
         for h in &s.game.heroes {
             pred_score[h.id] = (h.gold as f64 + (h.mine_count as usize * turns_left) as f64) +
-                               (h.life as f64 / 20f64);
+                               (h.life as f64 / w.life_per_gold);
 
             if h.name != s.hero.name {
                 let edist = s.game.board.shortest_path_length(&s.hero.pos, &h.pos);
-
-                if edist < 6 && edist != 3 && h.life / 20 <= s.hero.life / 20 {
-                    eval += 1.0;
-                } else if edist < 6 && edist != 3 && h.life / 20 > s.hero.life / 20 {
-                    eval -= 1.0;
-                } else if edist == 3 && h.life / 20 + 1 >= s.hero.life / 20 {
-                    eval -= 1.0;
+                let life = h.life / w.life_bucket;
+                let our_life = s.hero.life / w.life_bucket;
+
+                if edist < w.enemy_range && edist != w.enemy_standoff && life <= our_life {
+                    eval += w.near_bonus;
+                } else if edist < w.enemy_range && edist != w.enemy_standoff && life > our_life {
+                    eval -= w.near_penalty;
+                } else if edist == w.enemy_standoff && life + 1 >= our_life {
+                    eval -= w.near_penalty;
                 }
             }
         }
 
         for h in &s.game.heroes {
             for e in &s.game.heroes {
-                if h.elo == e.elo {
+                if h.id == e.id {
                     continue
                 }
 
@@ -77,10 +377,10 @@ impl Bot {
 
         let (mdist, _) = s.game.board.get_closest_mine(&s.hero.pos, s.hero.id);
         let delay;
-        if mdist < 255 && (s.hero.life < mdist || s.hero.life - mdist <= 20) {
+        if mdist < 255 && (s.hero.life < mdist || s.hero.life - mdist <= w.thirst_margin) {
             let (tdist, tpos) = s.game.board.get_closest_tavern(&s.hero.pos);
             let (mdist2, _) = s.game.board.get_closest_mine(&tpos, s.hero.id);
-            delay = 2 + (tdist + mdist2) as usize;
+            delay = w.tavern_delay as usize + (tdist + mdist2) as usize;
         } else if mdist < 255 {
             delay = mdist as usize;
         } else {
@@ -94,7 +394,7 @@ impl Bot {
         for h in &s.game.heroes {
             if h.name == s.hero.name {
                 eval += pred_score[h.id];
-                eval += rank_adj[h.id] * 10000.0;
+                eval += rank_adj[h.id] * w.rank_weight;
             } else {
                 eval -= pred_score[h.id];
             }
@@ -182,37 +482,51 @@ impl Bot {
             return Move::default();
         }
 
-        let mut best_score = 0;
+        let killers = if self.ordering.killers {
+            self.killers.get(s.game.turn)
+        } else {
+            [None; 2]
+        };
+
+        let counter = match self.move_stack.last() {
+            Some(prev) if self.ordering.counter_moves => {
+                let (id, pos, _) = mover(s, prev);
+                self.counter_moves.get(id, &pos)
+            }
+            _ => None,
+        };
+
+        // Killers first, then the most threatening opponent, with the counter
+        // move and the history score breaking ties.
+        let mut best_score = 0u64;
         let mut best_idx = 0;
         for (i, mv) in moves.iter().enumerate() {
-            let mut score = 0;
+            let mut score = 0u64;
 
             if mv == hm && *hm != Move::default() {
                 best_idx = i;
                 break;
-            } else if (*mv).player == self.threat_list[0] {
-                score = 5;
-            } else if (*mv).player == self.threat_list[1] {
-                score = 3;
-            } else if (*mv).player == self.threat_list[2] {
-                score = 1;
-            }
-
-            if (*mv).player == 0 {
-                let hist_score = 6 + self.max_history.query((s.hero.pos, mv.directions[0]));
-                if hist_score != 255 {
-                    score += hist_score;
-                }
-            } else {
-                let hist_score =
-                    6 +
-                    self.min_history
-                        .query(((*mv).player,
-                                s.game.heroes[(s.hero.id + (*mv).player as usize - 1) % 4].pos,
-                                (*mv).directions[(*mv).player as usize]));
-                if hist_score != 255 {
-                    score += hist_score;
-                }
+            }
+
+            if killers[0] == Some(*mv) {
+                score += 5 << 32;
+            } else if killers[1] == Some(*mv) {
+                score += 4 << 32;
+            } else if counter == Some(*mv) {
+                score += 1 << 23;
+            }
+
+            if mv.player == self.threat_list[0] {
+                score += 3 << 24;
+            } else if mv.player == self.threat_list[1] {
+                score += 2 << 24;
+            } else if mv.player == self.threat_list[2] {
+                score += 1 << 24;
+            }
+
+            if self.ordering.history {
+                let (id, pos, dir) = mover(s, mv);
+                score += self.history.score(id, &pos, dir) as u64;
             }
 
             if score > best_score {
@@ -224,7 +538,23 @@ impl Bot {
         moves.swap_remove(best_idx)
     }
 
-    fn brs(&mut self,
+    /// Remembers `mv` as the best move at `s`. Moves that caused a cutoff
+    /// also become killers and the counter move to the previous move.
+    fn update_ordering(&mut self, s: &State, mv: &Move, depth: u8, cutoff: bool) {
+        let (id, pos, dir) = mover(s, mv);
+        self.history.reward(id, &pos, dir, depth);
+
+        if cutoff {
+            self.killers.insert(s.game.turn, *mv);
+
+            if let Some(prev) = self.move_stack.last() {
+                let (id, pos, _) = mover(s, prev);
+                self.counter_moves.set(id, &pos, *mv);
+            }
+        }
+    }
+
+    pub fn brs(&mut self,
            s: &mut State,
            alphao: i32,
            betao: i32,
@@ -239,9 +569,7 @@ impl Bot {
         let mut a: i32;
         let mut b: i32;
 
-        let mut sh = FnvHasher::default();
-        s.hash(&mut sh);
-        let hash = sh.finish();
+        let hash = position_hash(s);
         let entry = self.tt.probe(hash);
 
         if entry.is_some() {
@@ -270,18 +598,22 @@ impl Bot {
 
         *nodes += 1;
 
-        if (*nodes < 10u64 || *nodes & 1023u64 == 1023u64) && time::get_time() > end_time {
+        if self.out_of_time(*nodes, end_time) {
             return None;
         }
 
-        if depth == 0 || s.game.turn > s.game.max_turns - 4 {
+        if s.game.turn > s.game.max_turns - 4 {
             g = self.eval(s);
+        } else if depth == 0 {
+            let plies = self.quiescence_plies;
+            g = self.quiesce(s, alpha, beta, plies, end_time, nodes)?;
         } else if s.game.turn % 4 == s.hero.id - 1 {
             let mut bscore = i32::min_value();
             g = i32::min_value();
             a = alpha;
 
             let mut moves = self.generate_moves(s);
+            let mut searched = 0;
 
             while !moves.is_empty() {
                 if g >= beta {
@@ -289,9 +621,21 @@ impl Bot {
                 }
 
                 let curmove = self.pick_next_move(&bmove, &mut moves, s);
+                let before = danger(s);
                 let umi = s.make_move(curmove.directions[0]);
-                let v = self.brs(s, a, beta, depth - 1, end_time, nodes);
+                self.move_stack.push(curmove);
+                let child = self.child_depth(s, before, depth, searched);
+                let window = Window {
+                    alpha: a,
+                    beta: beta,
+                };
+                let v = self.search_max_child(s, window, child, searched == 0, end_time, nodes);
+                if child.extended {
+                    self.path_extensions -= 1;
+                }
+                self.move_stack.pop();
                 s.unmake_move(umi);
+                searched += 1;
 
                 if v.is_none() {
                     return None;
@@ -301,9 +645,6 @@ impl Bot {
                 if score > bscore {
                     bmove = curmove;
                     bscore = score;
-
-                    self.max_history
-                        .insert((s.hero.pos, bmove.directions[0]));
                 }
                 if score > g {
                     g = score;
@@ -312,12 +653,19 @@ impl Bot {
                     a = g
                 }
             }
+
+            if bscore != i32::min_value() {
+                self.update_ordering(s, &bmove, depth, g >= beta);
+            }
+        } else if let Some(v) = self.null_move(s, alpha, depth, end_time, nodes)? {
+            g = v;
         } else {
             let mut bscore = i32::max_value();
             g = i32::max_value();
             b = beta;
 
             let mut moves = self.generate_moves(s);
+            let mut searched = 0;
 
             while !moves.is_empty() {
                 if g <= alpha {
@@ -325,15 +673,27 @@ impl Bot {
                 }
 
                 let curmove = self.pick_next_move(&bmove, &mut moves, s);
+                let before = danger(s);
                 let umi1 = s.make_move(curmove.directions[1]);
                 let umi2 = s.make_move(curmove.directions[2]);
                 let umi3 = s.make_move(curmove.directions[3]);
+                self.move_stack.push(curmove);
+
+                let child = self.child_depth(s, before, depth, searched);
+                let window = Window {
+                    alpha: alpha,
+                    beta: b,
+                };
+                let v = self.search_min_child(s, window, child, searched == 0, end_time, nodes);
+                if child.extended {
+                    self.path_extensions -= 1;
+                }
 
-                let v = self.brs(s, alpha, b, depth - 1, end_time, nodes);
-
+                self.move_stack.pop();
                 s.unmake_move(umi3);
                 s.unmake_move(umi2);
                 s.unmake_move(umi1);
+                searched += 1;
 
                 if v.is_none() {
                     return None;
@@ -343,11 +703,6 @@ impl Bot {
                 if score < bscore {
                     bmove = curmove;
                     bscore = score;
-
-                    self.min_history
-                        .insert((bmove.player,
-                                 s.game.heroes[(s.hero.id - 1 + bmove.player as usize) % 4].pos,
-                                 bmove.directions[0]));
                 }
                 if score < g {
                     g = score;
@@ -356,6 +711,10 @@ impl Bot {
                     b = g;
                 }
             }
+
+            if bscore != i32::max_value() {
+                self.update_ordering(s, &bmove, depth, g <= alpha);
+            }
         }
 
         if bmove.player != 0 {
@@ -378,132 +737,645 @@ impl Bot {
             }
         }
 
-        let mut e = Entry::default();
-        if g <= alpha {
-            e.upper = g;
-            e.lower = i32::min_value();
-            e.mv = bmove;
-        } else if g > alpha && g < beta {
-            e.upper = g;
-            e.lower = g;
-            e.mv = bmove;
-        } else if g >= beta {
-            e.lower = g;
-            e.upper = i32::max_value();
-            e.mv = bmove;
+        let window = Window {
+            alpha: alpha,
+            beta: beta,
+        };
+        self.store(s, hash, depth, g, window, bmove);
+
+        Some(g)
+    }
+
+    /// The depths to search a move to that was just played, given the
+    /// danger our hero was in before it and the number of moves searched
+    /// before it: the full depth, one ply more if the move put our hero in
+    /// danger, and the depth to try first, less for late moves. Counts an
+    /// extension on the path if there is one.
+    fn child_depth(&mut self,
+                   s: &State,
+                   before: (u8, usize),
+                   depth: u8,
+                   searched: usize)
+                   -> ChildDepth {
+        let after = danger(s);
+        let endangered = (after.0 <= DANGER_LIFE && before.0 > DANGER_LIFE) || after.1 > before.1;
+
+        if self.extensions && endangered && self.path_extensions < MAX_EXTENSIONS {
+            self.path_extensions += 1;
+            self.stats.extensions += 1;
+            return ChildDepth {
+                full: depth,
+                reduced: depth,
+                extended: true,
+            };
         }
 
-        e.depth = s.game.turn as u16 + depth as u16;
-        e.hash = hash;
-        e.age = s.game.turn as u16;
+        let full = depth - 1;
+        let mut reduced = full;
+        if self.reductions && !endangered && depth >= LMR_MIN_DEPTH && searched >= LMR_FULL_MOVES {
+            let reduction = if searched >= LMR_LATE_MOVES { 2 } else { 1 };
+            reduced = full.saturating_sub(reduction).max(1);
+        }
 
-        self.tt.store(e);
+        ChildDepth {
+            full: full,
+            reduced: reduced,
+            extended: false,
+        }
+    }
 
-        Some(g)
+    /// Searches a move at a MAX node with the window (`a`, `beta`). The first
+    /// move gets the full window; a reduced later one has to show with a null
+    /// window that it beats the best move so far before it is searched to
+    /// the full depth. With a strategy that uses Principal Variation Search,
+    /// later moves also have to show that at the full depth before they get
+    /// the full window.
+    fn search_max_child(&mut self,
+                        s: &mut State,
+                        window: Window,
+                        child: ChildDepth,
+                        first: bool,
+                        end_time: time::Timespec,
+                        nodes: &mut u64)
+                        -> Option<i32> {
+        let Window { alpha: a, beta } = window;
+        let ChildDepth { full, reduced, .. } = child;
+
+        if first {
+            return self.brs(s, a, beta, full, end_time, nodes);
+        }
+
+        if reduced < full {
+            self.stats.reductions += 1;
+            let v = self.brs(s, a, a + 1, reduced, end_time, nodes)?;
+            if v <= a {
+                return Some(v);
+            }
+            self.stats.researches += 1;
+        }
+
+        if !self.strategy.principal_variation() || beta as i64 - a as i64 <= 1 {
+            return self.brs(s, a, beta, full, end_time, nodes);
+        }
+
+        // Principal Variation Search: show with a null window that the move
+        // is no better than the best one so far.
+        match self.brs(s, a, a + 1, full, end_time, nodes) {
+            Some(v) if v > a && v < beta => {
+                self.stats.researches += 1;
+                self.brs(s, a, beta, full, end_time, nodes)
+            }
+            v => v,
+        }
     }
 
-    pub fn mtdf(&mut self,
-                s: &mut State,
-                firstguess: i32,
-                depth: u8,
-                mut num_nodes: &mut u64,
-                end_time: time::Timespec)
-                -> Option<i32> {
-        let mut f = firstguess;
-        let mut upper = i32::max_value();
-        let mut lower = i32::min_value();
-        let step_size = 25i32;
+    /// Like search_max_child, for a move at a MIN node with the window
+    /// (`alpha`, `b`).
+    fn search_min_child(&mut self,
+                        s: &mut State,
+                        window: Window,
+                        child: ChildDepth,
+                        first: bool,
+                        end_time: time::Timespec,
+                        nodes: &mut u64)
+                        -> Option<i32> {
+        let Window { alpha, beta: b } = window;
+        let ChildDepth { full, reduced, .. } = child;
+
+        if first {
+            return self.brs(s, alpha, b, full, end_time, nodes);
+        }
 
-        while upper == i32::max_value() || lower == i32::min_value() {
-            let val = self.brs(s, f - 1, f, depth, end_time, &mut num_nodes);
-            if val.is_none() {
-                return None;
+        if reduced < full {
+            self.stats.reductions += 1;
+            let v = self.brs(s, b - 1, b, reduced, end_time, nodes)?;
+            if v >= b {
+                return Some(v);
             }
+            self.stats.researches += 1;
+        }
+
+        if !self.strategy.principal_variation() || b as i64 - alpha as i64 <= 1 {
+            return self.brs(s, alpha, b, full, end_time, nodes);
+        }
 
-            let g = val.unwrap();
+        match self.brs(s, b - 1, b, full, end_time, nodes) {
+            Some(v) if v < b && v > alpha => {
+                self.stats.researches += 1;
+                self.brs(s, alpha, b, full, end_time, nodes)
+            }
+            v => v,
+        }
+    }
 
-            if g < f { upper = g } else { lower = g }
+    /// Null move pruning at a MIN node: the opponents pass, and if our score
+    /// still doesn't get above `alpha` in a search NULL_REDUCTION plies
+    /// shallower, their best move is assumed to keep it there too. Staying
+    /// is always allowed, so passing can't be better for them than their
+    /// best move; only the shallower search can be wrong. Cutoffs are
+    /// verified by searching the real moves one ply shallower, without
+    /// passing.
+    ///
+    /// Returns the score to fail low with, Some(None) if the node has to be
+    /// searched, and None if the search was stopped.
+    fn null_move(&mut self,
+                 s: &mut State,
+                 alpha: i32,
+                 depth: u8,
+                 end_time: time::Timespec,
+                 nodes: &mut u64)
+                 -> Option<Option<i32>> {
+        let pass = Move {
+            player: 1,
+            directions: [Direction::Stay; 4],
+        };
+
+        // No passing with a fight going on, close to the end of the game or
+        // right after the opponents passed before.
+        if !self.null_moves || depth < NULL_MIN_DEPTH || alpha == i32::min_value() ||
+           s.game.max_turns - s.game.turn <= 4 * depth as usize ||
+           self.move_stack.iter().rev().nth(1) == Some(&pass) || !self.is_quiet(s) ||
+           self.eval(s) > alpha {
+            return Some(None);
+        }
 
-            if upper == g {
-                f = g - step_size;
-            } else {
-                f = g + step_size;
+        self.stats.null_moves += 1;
+        let umi1 = s.make_move(Direction::Stay);
+        let umi2 = s.make_move(Direction::Stay);
+        let umi3 = s.make_move(Direction::Stay);
+        self.move_stack.push(pass);
+
+        let v = self.brs(s, alpha, alpha + 1, depth - 1 - NULL_REDUCTION, end_time, nodes);
+
+        self.move_stack.pop();
+        s.unmake_move(umi3);
+        s.unmake_move(umi2);
+        s.unmake_move(umi1);
+
+        if v? > alpha {
+            return Some(None);
+        }
+
+        self.null_moves = false;
+        let v = self.brs(s, alpha, alpha + 1, depth - 1, end_time, nodes);
+        self.null_moves = true;
+
+        let v = v?;
+        if v > alpha {
+            return Some(None);
+        }
+
+        self.stats.null_cutoffs += 1;
+        Some(Some(v))
+    }
+
+    /// Whether `s` can be evaluated as it is: no enemy can reach our hero
+    /// with its next move, and our hero isn't next to a tavern or a mine with
+    /// so little life that what it does there decides its fate.
+    fn is_quiet(&mut self, s: &mut State) -> bool {
+        let hero = s.hero.clone();
+
+        for e in &s.game.heroes {
+            if e.id != hero.id && !e.crashed &&
+               s.game.board.shortest_path_length(&hero.pos, &e.pos) <= 2 {
+                return false;
             }
         }
 
-        if lower == upper {
-            return Some(lower);
+        if hero.life <= 25 {
+            for n in &hero.pos.neighbors() {
+                match s.game.board.tile_at(n) {
+                    Tile::Tavern => return false,
+                    Tile::Mine(x) if x != hero.id => return false,
+                    _ => (),
+                }
+            }
         }
 
-        self.brs(s, lower, upper, depth, end_time, &mut num_nodes)
+        true
+    }
+
+    /// Searches on past the nominal depth until the position is quiet, so
+    /// the horizon doesn't cut fights in half. Only our moves and those of
+    /// enemies close to us are searched, and either side may stand pat with
+    /// the static evaluation, since staying is always possible.
+    fn quiesce(&mut self,
+               s: &mut State,
+               alpha: i32,
+               beta: i32,
+               plies: u8,
+               end_time: time::Timespec,
+               nodes: &mut u64)
+               -> Option<i32> {
+        let stand_pat = self.eval(s);
+
+        if plies == 0 || s.game.turn > s.game.max_turns - 4 || self.is_quiet(s) {
+            return Some(stand_pat);
+        }
+
+        *nodes += 1;
+        self.stats.quiescence_nodes += 1;
+        if self.out_of_time(*nodes, end_time) {
+            return None;
+        }
+
+        let mut g = stand_pat;
+
+        if s.game.turn % 4 == s.hero.id - 1 {
+            let mut a = alpha.max(g);
+
+            for dir in s.get_moves() {
+                if g >= beta {
+                    break;
+                }
+
+                let umi = s.make_move(dir);
+                let v = self.quiesce(s, a, beta, plies - 1, end_time, nodes);
+                s.unmake_move(umi);
+
+                g = g.max(v?);
+                a = a.max(g);
+            }
+        } else {
+            let mut b = beta.min(g);
+            let hero = s.hero.clone();
+
+            for mv in self.generate_moves(s) {
+                if g <= alpha {
+                    break;
+                }
+
+                let mover = &s.game.heroes[(hero.id - 1 + mv.player as usize) % 4];
+                if mv.directions[mv.player as usize] == Direction::Stay ||
+                   s.game.board.shortest_path_length(&hero.pos, &mover.pos) > 3 {
+                    continue;
+                }
+
+                let umi1 = s.make_move(mv.directions[1]);
+                let umi2 = s.make_move(mv.directions[2]);
+                let umi3 = s.make_move(mv.directions[3]);
+
+                let v = self.quiesce(s, alpha, b, plies - 1, end_time, nodes);
+
+                s.unmake_move(umi3);
+                s.unmake_move(umi2);
+                s.unmake_move(umi1);
+
+                g = g.min(v?);
+                b = b.min(g);
+            }
+        }
+
+        Some(g)
+    }
+
+    /// Stores the result `g` of searching `s` to `depth` with `window`.
+    pub fn store(&self, s: &State, hash: u64, depth: u8, g: i32, window: Window, mv: Move) {
+        let Window { alpha, beta } = window;
+        let mut e = Entry::default();
+        if g <= alpha {
+            e.upper = g;
+            e.lower = i32::min_value();
+        } else if g < beta {
+            e.upper = g;
+            e.lower = g;
+        } else {
+            e.lower = g;
+            e.upper = i32::max_value();
+        }
+
+        e.mv = mv;
+        e.depth = s.game.turn as u16 + depth as u16;
+        e.hash = hash;
+        e.age = s.game.turn as u16;
+
+        self.tt.store(e);
     }
 
     pub fn choose_move(&mut self, s: &mut State) -> Direction {
-        let end_time = time::get_time() + time::Duration::milliseconds(800);
+        self.stop.store(false, Ordering::Relaxed);
+        self.initialize(s);
+        self.with_helpers(s, |bot, s| bot.search(s))
+    }
 
+    fn initialize(&mut self, s: &State) {
         if !self.initialized {
             for h in &s.game.heroes {
                 for enemy in &s.game.heroes {
-                    if h.elo == enemy.elo {
+                    if h.id == enemy.id {
                         continue;
                     }
 
-                    let q_self = f64::powf(10.0, h.elo as f64 / 400.0);
-                    let q_enemy = f64::powf(10.0, enemy.elo as f64 / 400.0);
+                    let q_self = f64::powf(10.0, h.rating() as f64 / 400.0);
+                    let q_enemy = f64::powf(10.0, enemy.rating() as f64 / 400.0);
                     let expected_self = q_self / (q_self + q_enemy);
 
-                    self.elo_cache[s.hero.id - 1][enemy.id - 1][0] = 16.0 * (1.0 - expected_self);
-                    self.elo_cache[s.hero.id - 1][enemy.id - 1][1] = 16.0 * (0.5 - expected_self);
-                    self.elo_cache[s.hero.id - 1][enemy.id - 1][2] = 16.0 * (0.0 - expected_self);
+                    let k = self.weights.elo_k;
+                    self.elo_cache[h.id - 1][enemy.id - 1][0] = k * (1.0 - expected_self);
+                    self.elo_cache[h.id - 1][enemy.id - 1][1] = k * (0.5 - expected_self);
+                    self.elo_cache[h.id - 1][enemy.id - 1][2] = k * (0.0 - expected_self);
                 }
             }
 
             self.initialized = true;
         }
+    }
+
+    /// Runs `f` on this bot while the helper threads search clones of `s`,
+    /// and stops the helpers when it returns.
+    fn with_helpers<F, R>(&mut self, s: &mut State, f: F) -> R
+        where F: FnOnce(&mut Bot, &mut State) -> R
+    {
+        if self.helpers.is_empty() {
+            return f(self, s);
+        }
 
-        // Clear history
-        self.max_history = LRU::new((Position { x: -1, y: -1 }, Direction::Stay));
-        self.min_history = LRU::new((4u8, Position { x: -1, y: -1 }, Direction::Stay));
+        let mut helpers = mem::replace(&mut self.helpers, Vec::new());
+        let stop = self.stop.clone();
+
+        let result = thread::scope(|scope| {
+            for (i, h) in helpers.iter_mut().enumerate() {
+                h.elo_cache = self.elo_cache;
+                h.initialized = true;
+
+                // Starting every other helper one ply deeper spreads the
+                // threads over more of the tree.
+                let mut hs = s.clone();
+                scope.spawn(move || h.search_until_stopped(&mut hs, 1 + (i % 2) as u8));
+            }
+
+            let result = f(self, s);
+            stop.store(true, Ordering::Relaxed);
+            result
+        });
+
+        self.helpers = helpers;
+        result
+    }
+
+    /// Killers only apply to the search they were found in, while history
+    /// scores fade over a few searches.
+    fn start_ordering(&mut self) {
+        self.history.age();
+        self.killers.clear();
+        self.move_stack.clear();
+        self.path_extensions = 0;
+    }
+
+    fn search(&mut self, s: &mut State) -> Direction {
+        let start_time = time::get_time();
+        let end_time = start_time + time::Duration::milliseconds(self.move_time);
+
+        self.stats = SearchStats::default();
+
+        self.start_ordering();
 
         let mut depth = 0u8;
         let mut num_nodes = 0u64;
         let mut firstguess = self.eval(s);
 
+        // The move, depth, score and PV of the last iteration that completed
+        // and left its move in the table, so they all describe the same line.
         let mut best_d = Direction::Stay;
-        let mut prev_b = Direction::Stay;
+        let mut completed = 0u8;
+        let mut score = firstguess;
+        let mut pv = Vec::new();
+        let mut last_iteration = time::Duration::zero();
 
-        while time::get_time() < end_time && depth < 32 {
+        while time::get_time() < end_time && depth < self.max_depth {
             depth += 1;
-            let v = self.mtdf(s, firstguess, depth, &mut num_nodes, end_time);
+            let iteration_start = time::get_time();
+            let strategy = self.strategy.clone();
+            let v = strategy.search(self, s, firstguess, depth, &mut num_nodes, end_time);
             if v.is_some() {
                 firstguess = v.unwrap();
 
-                let mut sh = FnvHasher::default();
-                s.hash(&mut sh);
-                let hash = sh.finish();
-
-                let entry = self.tt.probe(hash);
+                let entry = self.tt.probe(position_hash(s));
                 if entry.is_some() {
                     let e = entry.unwrap();
-                    println!("{}: [{}, {}], {}",
-                             e.mv.directions[0],
-                             e.lower,
-                             e.upper,
-                             e.depth - s.game.turn as u16);
-                    prev_b = best_d;
+                    pv = self.principal_variation(s, depth as usize)
+                        .iter()
+                        .map(|mv| mv.describe(s.hero.id))
+                        .collect();
+
+                    if self.verbose {
+                        println!("{}: [{}, {}], {}, pv: {}",
+                                 e.mv.directions[0],
+                                 e.lower,
+                                 e.upper,
+                                 e.depth - s.game.turn as u16,
+                                 pv.join(", "));
+                    }
                     best_d = e.mv.directions[0];
+                    completed = depth;
+                    score = firstguess;
+                }
+
+                // Don't start an iteration that can't finish in time. Each one
+                // takes a few times as long as the one before it.
+                let iteration = time::get_time() - iteration_start;
+                let growth = if last_iteration.num_microseconds().unwrap_or(0) > 0 {
+                    let ratio = iteration.num_microseconds().unwrap_or(0) as f64 /
+                                last_iteration.num_microseconds().unwrap_or(1) as f64;
+                    ratio.max(1.5).min(8.0)
+                } else {
+                    4.0
+                };
+                last_iteration = iteration;
+
+                let predicted = (iteration.num_microseconds().unwrap_or(0) as f64 * growth) as i64;
+                if time::get_time() + time::Duration::microseconds(predicted) > end_time {
+                    break;
+                }
+            }
+        }
+
+        if self.verbose {
+            println!("{}, {} - {} - {} - {}, nodes: {}, root searches: {}, re-searches: {}, \
+                      reductions: {}, extensions: {}",
+                     completed,
+                     best_d,
+                     score,
+                     end_time - time::get_time(),
+                     s.hero.life,
+                     num_nodes,
+                     self.stats.root_searches,
+                     self.stats.researches,
+                     self.stats.reductions,
+                     self.stats.extensions);
+        }
+
+        self.last_search = SearchInfo {
+            depth: completed,
+            score: score,
+            nodes: num_nodes,
+            time_ms: (time::get_time() - start_time).num_milliseconds(),
+            best: best_d,
+            pv: pv,
+            stats: self.stats,
+        };
+
+        best_d
+    }
+
+    /// Follows the moves stored in the transposition table from `s`, for at
+    /// most `max_len` moves. Stops early when a position is missing from the
+    /// table or comes up a second time.
+    pub fn principal_variation(&self, s: &State, max_len: usize) -> Vec<Move> {
+        let mut s = s.clone();
+        let mut result = Vec::with_capacity(max_len);
+        let mut seen = HashSet::new();
+
+        while result.len() < max_len && !s.game.finished {
+            let hash = position_hash(&s);
+
+            if !seen.insert(hash) {
+                break;
+            }
+
+            let mv = match self.tt.probe(hash) {
+                Some(e) => e.mv,
+                None => break,
+            };
+
+            self.strategy.play(&mut s, &mv);
+            result.push(mv);
+        }
+
+        result
+    }
+
+    /// Searches every legal move at the root to `depth` to its exact score,
+    /// so the scores can be compared with each other. Meant for analysis
+    /// after choose_move, whose entries in the table make this cheap.
+    pub fn root_moves(&mut self, s: &mut State, depth: u8) -> Vec<RootMove> {
+        let end_time = time::get_time() + time::Duration::days(1);
+        let mut result = Vec::new();
+        self.stop.store(false, Ordering::Relaxed);
+
+        for dir in &s.get_moves() {
+            let mut nodes = 0u64;
+            let umi = s.make_move(*dir);
+            let guess = self.eval(s);
+            let strategy = self.strategy.clone();
+            let v = strategy.search(self, s, guess, depth.saturating_sub(1), &mut nodes, end_time);
+            s.unmake_move(umi);
+
+            result.push(RootMove {
+                dir: *dir,
+                score: v.unwrap_or(0),
+                nodes: nodes,
+            });
+        }
+
+        result.sort_by(|a, b| b.score.cmp(&a.score));
+        result
+    }
+
+    /// The position after `dir` and the replies the table expects, i.e. the
+    /// position we will most likely be asked about next.
+    pub fn predicted_state(&self, s: &State, dir: Direction) -> State {
+        let mut p = s.clone();
+        p.make_move(dir);
+
+        while p.game.turn % 4 != p.hero.id - 1 && !p.game.finished {
+            match self.principal_variation(&p, 1).pop() {
+                Some(reply) => self.strategy.play(&mut p, &reply),
+                None => {
+                    p.make_move(Direction::Stay);
                 }
             }
         }
 
-        println!("{}, {} - {} - {} - {}, nodes: {}",
-                 depth,
-                 prev_b,
-                 firstguess,
-                 end_time - time::get_time(),
-                 s.hero.life,
-                 num_nodes);
+        p
+    }
+
+    /// Searches `s` until the stop flag is set, only to fill the
+    /// transposition table for the next call to choose_move.
+    pub fn ponder(&mut self, s: &mut State) {
+        let (depth, nodes) = self.with_helpers(s, |bot, s| bot.search_until_stopped(s, 1));
 
-        prev_b
+        if self.verbose {
+            println!("pondered to depth {}, nodes: {}", depth, nodes);
+        }
+    }
+
+    /// Iterative deepening from `first_depth` without a deadline. Returns the
+    /// last completed depth and the number of nodes searched.
+    fn search_until_stopped(&mut self, s: &mut State, first_depth: u8) -> (u8, u64) {
+        let end_time = time::get_time() + time::Duration::days(1);
+        self.start_ordering();
+        let mut guess = self.eval(s);
+        let mut nodes = 0u64;
+        let mut depth = first_depth - 1;
+
+        while depth < self.max_depth && !s.game.finished {
+            let strategy = self.strategy.clone();
+            match strategy.search(self, s, guess, depth + 1, &mut nodes, end_time) {
+                Some(v) => guess = v,
+                None => break,
+            }
+            depth += 1;
+        }
+
+        (depth, nodes)
+    }
+}
+
+/// The hero `mv` is played by, where it stands at `s` and where it goes.
+fn mover(s: &State, mv: &Move) -> (usize, Position, Direction) {
+    let player = mv.player as usize;
+    let id = (s.hero.id - 1 + player) % 4 + 1;
+    (id, s.game.heroes[id - 1].pos, mv.directions[player])
+}
+
+/// Our hero's life and the number of enemies next to it.
+fn danger(s: &State) -> (u8, usize) {
+    let adjacent = s.game
+        .heroes
+        .iter()
+        .filter(|e| {
+            e.id != s.hero.id && !e.crashed &&
+            (e.pos.x - s.hero.pos.x).abs() + (e.pos.y - s.hero.pos.y).abs() == 1
+        })
+        .count();
+
+    (s.hero.life, adjacent)
+}
+
+#[cfg(test)]
+mod tests {
+    use mapgen;
+    use state::State;
+    use super::Bot;
+
+    #[test]
+    fn last_search_describes_the_chosen_move() {
+        let names = [String::from("a"), String::from("b"), String::from("c"), String::from("d")];
+
+        for seed in 1..5 {
+            let board = mapgen::generate(mapgen::size_for_seed(seed), seed);
+            let mut s = State::new("test", board, 400, &names);
+
+            let mut bot = Bot::with_table_size(100000);
+            bot.set_verbose(false);
+            bot.set_move_time(60000);
+
+            // A few turns in, so that the heroes have left their spawn points.
+            for turn in 0..12 {
+                s.hero = s.game.heroes[turn % 4].clone();
+                let depth = 1 + (turn % 6) as u8;
+                bot.new_game();
+                bot.set_max_depth(depth);
+
+                let dir = bot.choose_move(&mut s);
+                let info = bot.last_search();
+                assert_eq!(info.best, dir);
+                assert_eq!(info.depth, depth);
+                assert_eq!(info.pv.first(), Some(&format!("@{} {}", s.hero.id, dir)));
+
+                s.make_move(dir);
+            }
+        }
     }
 }
@@ -0,0 +1,189 @@
+use std::error;
+use std::fmt;
+use std::io;
+use std::io::Read;
+use std::thread;
+use std::time::Duration;
+
+use hyper;
+use hyper::client::Client;
+use hyper::header::ContentType;
+use serde_json;
+
+use config::Config;
+use direction::Direction;
+use state::State;
+
+#[derive(Debug)]
+pub enum Error {
+    /// The server could not be reached, even after retrying.
+    Unreachable(hyper::Error),
+    /// The server answered with something other than 200 OK.
+    Rejected(u16, String),
+    /// The server answered 200 OK, but not with a game state.
+    InvalidResponse(String),
+}
+
+impl Error {
+    pub fn is_game_over(&self) -> bool {
+        match *self {
+            Error::Rejected(_, ref msg) => msg.to_lowercase().contains("finished"),
+            _ => false,
+        }
+    }
+
+    /// Whether the request could succeed if sent again: the server could not
+    /// be reached or had trouble of its own.
+    pub fn is_transient(&self) -> bool {
+        match *self {
+            Error::Unreachable(hyper::Error::Io(_)) => true,
+            Error::Rejected(status, _) => status >= 500,
+            _ => false,
+        }
+    }
+
+    /// Whether the request certainly never got to the server, so sending it
+    /// again can't make it count twice.
+    fn never_arrived(&self) -> bool {
+        match *self {
+            Error::Unreachable(hyper::Error::Io(ref e)) => {
+                e.kind() == io::ErrorKind::ConnectionRefused
+            }
+            _ => false,
+        }
+    }
+
+    pub fn is_crash(&self) -> bool {
+        match *self {
+            Error::Rejected(_, ref msg) => {
+                let msg = msg.to_lowercase();
+                msg.contains("time out") || msg.contains("timeout") || msg.contains("crashed")
+            }
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Unreachable(ref e) => write!(f, "server unreachable: {}", e),
+            Error::Rejected(status, ref msg) => write!(f, "server returned {}: {}", status, msg),
+            Error::InvalidResponse(ref msg) => write!(f, "invalid response: {}", msg),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+/// How a game ended from the client's point of view.
+pub enum GameEnd {
+    /// The game ran to completion. Holds the last state received.
+    Finished(State),
+    /// The server said the game is over before sending its final state.
+    /// Holds the last state received, whose gold and ranks aren't final.
+    Unconfirmed(State),
+    /// Our hero crashed or timed out on the server.
+    Crashed(String),
+    /// The game was lost to network or protocol trouble.
+    Failed(Error),
+}
+
+pub struct Connection {
+    client: Client,
+    queue_client: Client,
+    retries: u32,
+}
+
+impl Connection {
+    pub fn new(config: &Config) -> Connection {
+        let mut client = Client::new();
+        client.set_read_timeout(Some(Duration::from_secs(config.timeout)));
+        client.set_write_timeout(Some(Duration::from_secs(config.timeout)));
+
+        // Waiting for an arena game to fill up can take minutes, so the
+        // initial request must not time out on reads.
+        let mut queue_client = Client::new();
+        queue_client.set_write_timeout(Some(Duration::from_secs(config.timeout)));
+
+        Connection {
+            client: client,
+            queue_client: queue_client,
+            retries: config.retries,
+        }
+    }
+
+    pub fn start(&self, config: &Config) -> Result<State, Error> {
+        let body = self.post(&self.queue_client,
+                             &config.start_url(),
+                             &config.start_body(),
+                             true)?;
+        parse_state(&body)
+    }
+
+    /// Sends a move. A move the server got may already have counted, so it
+    /// is only sent again here if the connection was refused. After any other
+    /// error, the caller can only send the same move again and has to take
+    /// the server's next state as it is.
+    pub fn play(&self, state: &State, key: &str, dir: Direction) -> Result<State, Error> {
+        let dir: &'static str = dir.into();
+        let body = self.post(&self.client,
+                  &state.play_url,
+                  &format!("key={}&dir={}", key, dir),
+                  false)?;
+        parse_state(&body)
+    }
+
+    /// Retries transient errors with exponential backoff if the request is
+    /// `idempotent`, and otherwise only those where it never arrived.
+    fn post(&self,
+            client: &Client,
+            url: &str,
+            body: &str,
+            idempotent: bool)
+            -> Result<String, Error> {
+        let mut attempt = 0;
+        let mut backoff = Duration::from_millis(250);
+
+        loop {
+            match try_post(client, url, body) {
+                Ok(body) => return Ok(body),
+                Err(e) => {
+                    let retry = e.never_arrived() || (idempotent && e.is_transient());
+
+                    if !retry || attempt >= self.retries {
+                        return Err(e);
+                    }
+
+                    println!("{}, retrying in {:?}", e, backoff);
+                    thread::sleep(backoff);
+                    attempt += 1;
+                    backoff *= 2;
+                }
+            }
+        }
+    }
+}
+
+fn try_post(client: &Client, url: &str, body: &str) -> Result<String, Error> {
+    let mut res = client
+        .post(url)
+        .header(ContentType("application/x-www-form-urlencoded".parse().unwrap()))
+        .body(body)
+        .send()
+        .map_err(Error::Unreachable)?;
+
+    let mut text = String::new();
+    res.read_to_string(&mut text)
+        .map_err(|e| Error::Unreachable(hyper::Error::Io(e)))?;
+
+    if res.status != hyper::Ok {
+        return Err(Error::Rejected(res.status.to_u16(), text));
+    }
+
+    Ok(text)
+}
+
+fn parse_state(body: &str) -> Result<State, Error> {
+    serde_json::from_str(body).map_err(|e| Error::InvalidResponse(format!("{}", e)))
+}
@@ -0,0 +1,798 @@
+use std::fs::File;
+use std::io::Read;
+
+use mapgen;
+use ordering::MoveOrdering;
+use search;
+
+pub const USAGE: &str = "Usage: brs [options]
+       brs serve [serve options]
+       brs match [match options]
+       brs mapgen [mapgen options]
+       brs verify FILE...
+       brs replay FILE [--turn N]
+       brs analyze FILE [analyze options]
+       brs bench [bench options]
+       brs tune [tune options]
+       brs features FILE...
+
+Options:
+    --server URL       Vindinium server to play on (default: http://vindinium.org)
+    --key KEY          API key of the bot
+    --key-file FILE    Read the API key from FILE instead
+    --arena            Play in arena mode (default)
+    --training         Play in training mode
+    --turns N          Number of turns per hero (training only)
+    --map NAME         Map to play on, m1 to m6 on vindinium.org (training only)
+    --games N          Number of games to play, 0 plays forever (default: 1)
+    --timeout SECS     Network timeout per request (default: 30)
+    --retries N        Retries for failed requests (default: 5). A move is
+                       sent again unchanged, for the same turn
+    --move-timeout MS  The server's time limit per move, thinking time is
+                       planned around it and the latency (default: 1000)
+    --no-ponder        Don't search ahead while waiting for the server
+    --threads N        Number of search threads (default: 1)
+    --search NAME      Search algorithm: mtdf, pvs, brs, paranoid or maxn
+                       (default: mtdf)
+    --quiescence N     Plies to search on while heroes are fighting, 0 turns
+                       quiescence search off (default: 4)
+    --weights FILE     Read the evaluation weights from a JSON file
+    --model FILE       Evaluate with the linear or MLP model in FILE instead
+    --record-transitions FILE
+                       Append every server transition to FILE for brs verify
+    --replay-dir DIR   Write a replay of every game to DIR/<game id>.jsonl
+    -h, --help         Print this message
+
+Serve options (local Vindinium-compatible server):
+    --address ADDR     Address to listen on (default: 127.0.0.1:9000)
+    --maps DIR         Directory with the .map files offered by name (default: maps)
+    --move-timeout MS  Time limit per move, 0 disables it (default: 1000)
+
+Match options (headless games between bots, no server involved):
+    --players LIST     Four comma-separated players: beagle, mcts, greedy or
+                       random; beagle:NAME uses the search algorithm NAME and
+                       beagle@FILE (or beagle:NAME@FILE) the evaluation
+                       weights or model in FILE, and beagle+null-moves
+                       turns null move pruning on. Players with the same
+                       name play as a team (default:
+                       beagle,random,random,random)
+    --games N          Number of games to play (default: 10)
+    --turns N          Number of turns per hero (default: 300)
+    --map FILE         Map file to play on (default: generated maps)
+    --maps DIR         Play on random .map files from DIR instead
+    --seed N           Seed for the generated maps, game i uses N + i
+    --size N           Size of the generated maps (default: depends on the seed)
+    --move-time MS     Thinking time per move for beagle (default: 100)
+
+Mapgen options (print a random map in the .map format):
+    --size N           Map size, even and between 10 and 28 (default: depends on the seed)
+    --seed N           Random seed (default: random)
+
+Verify replays recorded transitions with the simulator and reports every
+state that doesn't match the recording.
+
+Replay shows a replay written with --replay-dir one turn at a time, starting
+at game turn N (default: 0). Type n or press Enter for the next turn, p for
+the previous one, g N or just N to jump to turn N, f/l for the first/last
+turn and q to quit.
+
+Features prints the evaluation features of every state in the given replays
+as CSV, along with the gold and rank our hero finished with, to train models
+for --model on. Replays of games that didn't finish are skipped.
+
+Analyze options (search a state saved in the server's JSON format):
+    --depth N          Stop after N plies (default: 32)
+    --time MS          Thinking time in milliseconds (default: 800, or no
+                       limit with --depth)
+    --threads N        Number of search threads (default: 1)
+    --search NAME      Search algorithm (default: mtdf)
+    --quiescence N     Plies of quiescence search (default: 4)
+    --weights FILE     Evaluation weights (default: built in)
+    --model FILE       Evaluate with a model instead
+
+Bench options (search fixed positions and count nodes):
+    --depth N          Depth to search to (default: 7)
+    --time MS          Search each position for MS milliseconds instead and
+                       report the depth reached
+    --search NAME      Search algorithm (default: mtdf)
+    --quiescence N     Plies of quiescence search (default: 4)
+    --weights FILE     Evaluation weights (default: built in)
+    --model FILE       Evaluate with a model instead
+    --without LIST     Comma-separated search features to turn off: the move
+                       ordering heuristics killers, counter-moves and
+                       history, and reductions or extensions
+    --null-moves       Turn null move pruning on
+
+Tune options (tune the evaluation weights with SPSA in games between two
+pairs of Beagles):
+    --iterations N     Number of SPSA iterations (default: 100)
+    --games N          Games per iteration, even: every map is played twice
+                       with the teams in each other's seats (default: 2)
+    --turns N          Number of turns per hero (default: 100)
+    --move-time MS     Thinking time per move (default: 20)
+    --seed N           Seed of the first generated map (default: random)
+    --weights FILE     Weights to start from (default: built in)
+    --out FILE         Where to write the tuned weights (default: tuned.json)
+    --verify-games N   Games of the tuned weights against the starting ones,
+                       even as well (default: 20)";
+
+const DEFAULT_SERVER: &str = "http://vindinium.org";
+
+const MAPS: [&str; 6] = ["m1", "m2", "m3", "m4", "m5", "m6"];
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Mode {
+    Arena,
+    Training,
+}
+
+impl From<Mode> for &'static str {
+    fn from(mode: Mode) -> &'static str {
+        match mode {
+            Mode::Arena => "arena",
+            Mode::Training => "training",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub server: String,
+    pub key: String,
+    pub mode: Mode,
+    pub turns: Option<usize>,
+    pub map: Option<String>,
+    pub games: usize,
+    pub timeout: u64,
+    pub retries: u32,
+    pub move_timeout: i64,
+    pub ponder: bool,
+    pub threads: usize,
+    pub search: String,
+    pub quiescence: u8,
+    pub weights: Option<String>,
+    pub model: Option<String>,
+    pub transitions: Option<String>,
+    pub replay_dir: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            server: String::from(DEFAULT_SERVER),
+            key: String::default(),
+            mode: Mode::Arena,
+            turns: None,
+            map: None,
+            games: 1,
+            timeout: 30,
+            retries: 5,
+            move_timeout: 1000,
+            ponder: true,
+            threads: 1,
+            search: String::from("mtdf"),
+            quiescence: 4,
+            weights: None,
+            model: None,
+            transitions: None,
+            replay_dir: None,
+        }
+    }
+}
+
+fn next_value<I: Iterator<Item = String>>(args: &mut I, opt: &str) -> Result<String, String> {
+    match args.next() {
+        Some(v) => Ok(v),
+        None => Err(format!("Missing value for {}", opt)),
+    }
+}
+
+fn parse_number<I: Iterator<Item = String>>(args: &mut I, opt: &str) -> Result<usize, String> {
+    let v = next_value(args, opt)?;
+    v.parse::<usize>().map_err(|_| format!("Invalid value for {}: {}", opt, v))
+}
+
+fn parse_search<I: Iterator<Item = String>>(args: &mut I, opt: &str) -> Result<String, String> {
+    let name = next_value(args, opt)?;
+    if !search::NAMES.contains(&name.as_ref()) {
+        return Err(format!("Unknown search algorithm: {}, expected one of {}",
+                           name,
+                           search::NAMES.join(", ")));
+    }
+    Ok(name)
+}
+
+fn parse_plies<I: Iterator<Item = String>>(args: &mut I, opt: &str) -> Result<u8, String> {
+    let plies = parse_number(args, opt)?;
+    if plies > 32 {
+        return Err(format!("{} must be at most 32", opt));
+    }
+    Ok(plies as u8)
+}
+
+fn parse_size<I: Iterator<Item = String>>(args: &mut I, opt: &str) -> Result<usize, String> {
+    let size = parse_number(args, opt)?;
+    if !(mapgen::MIN_SIZE..=mapgen::MAX_SIZE).contains(&size) || size % 2 != 0 {
+        return Err(format!("{} must be even and between {} and {}",
+                           opt,
+                           mapgen::MIN_SIZE,
+                           mapgen::MAX_SIZE));
+    }
+    Ok(size)
+}
+
+impl Config {
+    pub fn from_args<I: Iterator<Item = String>>(mut args: I) -> Result<Config, String> {
+        let mut config = Config::default();
+        let mut key_file = None;
+
+        while let Some(arg) = args.next() {
+            match arg.as_ref() {
+                "--server" => config.server = next_value(&mut args, &arg)?,
+                "--key" => config.key = next_value(&mut args, &arg)?,
+                "--key-file" => key_file = Some(next_value(&mut args, &arg)?),
+                "--arena" => config.mode = Mode::Arena,
+                "--training" => config.mode = Mode::Training,
+                "--turns" => config.turns = Some(parse_number(&mut args, &arg)?),
+                "--map" => config.map = Some(next_value(&mut args, &arg)?),
+                "--games" => config.games = parse_number(&mut args, &arg)?,
+                "--timeout" => config.timeout = parse_number(&mut args, &arg)? as u64,
+                "--retries" => config.retries = parse_number(&mut args, &arg)? as u32,
+                "--move-timeout" => config.move_timeout = parse_number(&mut args, &arg)? as i64,
+                "--no-ponder" => config.ponder = false,
+                "--threads" => config.threads = parse_number(&mut args, &arg)?,
+                "--search" => config.search = parse_search(&mut args, &arg)?,
+                "--quiescence" => config.quiescence = parse_plies(&mut args, &arg)?,
+                "--weights" => config.weights = Some(next_value(&mut args, &arg)?),
+                "--model" => config.model = Some(next_value(&mut args, &arg)?),
+                "--record-transitions" => config.transitions = Some(next_value(&mut args, &arg)?),
+                "--replay-dir" => config.replay_dir = Some(next_value(&mut args, &arg)?),
+                _ => return Err(format!("Unknown option: {}", arg)),
+            }
+        }
+
+        if let Some(path) = key_file {
+            let mut key = String::new();
+            File::open(&path)
+                .and_then(|mut f| f.read_to_string(&mut key))
+                .map_err(|e| format!("Could not read key file {}: {}", path, e))?;
+            config.key = String::from(key.trim());
+        }
+
+        if config.key.is_empty() {
+            return Err(String::from("No API key given, use --key or --key-file"));
+        }
+
+        if config.mode != Mode::Training && (config.turns.is_some() || config.map.is_some()) {
+            return Err(String::from("--turns and --map are only valid with --training"));
+        }
+
+        if config.turns == Some(0) {
+            return Err(String::from("--turns must be at least 1"));
+        }
+
+        if config.threads == 0 {
+            return Err(String::from("--threads must be at least 1"));
+        }
+
+        config.server = String::from(config.server.trim_end_matches('/'));
+
+        // Local servers may offer other maps, so only check the official one.
+        if let Some(ref map) = config.map {
+            if config.server == DEFAULT_SERVER && !MAPS.contains(&map.as_ref()) {
+                return Err(format!("Unknown map: {}, expected one of m1 to m6", map));
+            }
+        }
+
+        Ok(config)
+    }
+
+    pub fn start_url(&self) -> String {
+        let mode: &'static str = self.mode.into();
+        format!("{}/api/{}", self.server, mode)
+    }
+
+    pub fn start_body(&self) -> String {
+        let mut body = format!("key={}", self.key);
+
+        if let Some(turns) = self.turns {
+            body.push_str(&format!("&turns={}", turns));
+        }
+
+        if let Some(ref map) = self.map {
+            body.push_str(&format!("&map={}", map));
+        }
+
+        body
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ServerConfig {
+    pub address: String,
+    pub maps: String,
+    pub move_timeout: u64,
+}
+
+impl Default for ServerConfig {
+    fn default() -> ServerConfig {
+        ServerConfig {
+            address: String::from("127.0.0.1:9000"),
+            maps: String::from("maps"),
+            move_timeout: 1000,
+        }
+    }
+}
+
+impl ServerConfig {
+    pub fn from_args<I: Iterator<Item = String>>(mut args: I) -> Result<ServerConfig, String> {
+        let mut config = ServerConfig::default();
+
+        while let Some(arg) = args.next() {
+            match arg.as_ref() {
+                "--address" => config.address = next_value(&mut args, &arg)?,
+                "--maps" => config.maps = next_value(&mut args, &arg)?,
+                "--move-timeout" => config.move_timeout = parse_number(&mut args, &arg)? as u64,
+                _ => return Err(format!("Unknown option: {}", arg)),
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MatchConfig {
+    pub players: Vec<String>,
+    pub games: usize,
+    pub turns: usize,
+    pub map: Option<String>,
+    pub maps: Option<String>,
+    pub seed: Option<u64>,
+    pub size: Option<usize>,
+    pub move_time: i64,
+}
+
+impl Default for MatchConfig {
+    fn default() -> MatchConfig {
+        MatchConfig {
+            players: vec![String::from("beagle"),
+                          String::from("random"),
+                          String::from("random"),
+                          String::from("random")],
+            games: 10,
+            turns: 300,
+            map: None,
+            maps: None,
+            seed: None,
+            size: None,
+            move_time: 100,
+        }
+    }
+}
+
+impl MatchConfig {
+    pub fn from_args<I: Iterator<Item = String>>(mut args: I) -> Result<MatchConfig, String> {
+        let mut config = MatchConfig::default();
+
+        while let Some(arg) = args.next() {
+            match arg.as_ref() {
+                "--players" => {
+                    config.players = next_value(&mut args, &arg)?
+                        .split(',')
+                        .map(String::from)
+                        .collect()
+                }
+                "--games" => config.games = parse_number(&mut args, &arg)?,
+                "--turns" => config.turns = parse_number(&mut args, &arg)?,
+                "--map" => config.map = Some(next_value(&mut args, &arg)?),
+                "--maps" => config.maps = Some(next_value(&mut args, &arg)?),
+                "--seed" => config.seed = Some(parse_number(&mut args, &arg)? as u64),
+                "--size" => config.size = Some(parse_size(&mut args, &arg)?),
+                "--move-time" => config.move_time = parse_number(&mut args, &arg)? as i64,
+                _ => return Err(format!("Unknown option: {}", arg)),
+            }
+        }
+
+        if config.players.len() != 4 {
+            return Err(format!("Expected four players, got {}", config.players.len()));
+        }
+
+        if config.turns == 0 {
+            return Err(String::from("--turns must be at least 1"));
+        }
+
+        Ok(config)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct MapgenConfig {
+    pub size: Option<usize>,
+    pub seed: Option<u64>,
+}
+
+impl MapgenConfig {
+    pub fn from_args<I: Iterator<Item = String>>(mut args: I) -> Result<MapgenConfig, String> {
+        let mut config = MapgenConfig::default();
+
+        while let Some(arg) = args.next() {
+            match arg.as_ref() {
+                "--size" => config.size = Some(parse_size(&mut args, &arg)?),
+                "--seed" => config.seed = Some(parse_number(&mut args, &arg)? as u64),
+                _ => return Err(format!("Unknown option: {}", arg)),
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ReplayConfig {
+    pub path: String,
+    pub turn: Option<usize>,
+}
+
+impl ReplayConfig {
+    pub fn from_args<I: Iterator<Item = String>>(mut args: I) -> Result<ReplayConfig, String> {
+        let mut config = ReplayConfig::default();
+
+        while let Some(arg) = args.next() {
+            match arg.as_ref() {
+                "--turn" => config.turn = Some(parse_number(&mut args, &arg)?),
+                _ if arg.starts_with("--") => return Err(format!("Unknown option: {}", arg)),
+                _ if config.path.is_empty() => config.path = arg,
+                _ => return Err(format!("Unexpected argument: {}", arg)),
+            }
+        }
+
+        if config.path.is_empty() {
+            return Err(String::from("No replay file given"));
+        }
+
+        Ok(config)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct AnalyzeConfig {
+    pub path: String,
+    pub depth: Option<u8>,
+    pub move_time: i64,
+    pub threads: usize,
+    pub search: String,
+    pub quiescence: u8,
+    pub weights: Option<String>,
+    pub model: Option<String>,
+}
+
+impl AnalyzeConfig {
+    pub fn from_args<I: Iterator<Item = String>>(mut args: I) -> Result<AnalyzeConfig, String> {
+        let mut path = None;
+        let mut depth = None;
+        let mut move_time = None;
+        let mut threads = 1;
+        let mut search = String::from("mtdf");
+        let mut quiescence = 4;
+        let mut weights = None;
+        let mut model = None;
+
+        while let Some(arg) = args.next() {
+            match arg.as_ref() {
+                "--depth" => depth = Some(parse_number(&mut args, &arg)?),
+                "--time" => move_time = Some(parse_number(&mut args, &arg)? as i64),
+                "--threads" => threads = parse_number(&mut args, &arg)?,
+                "--search" => search = parse_search(&mut args, &arg)?,
+                "--quiescence" => quiescence = parse_plies(&mut args, &arg)?,
+                "--weights" => weights = Some(next_value(&mut args, &arg)?),
+                "--model" => model = Some(next_value(&mut args, &arg)?),
+                _ if arg.starts_with("--") => return Err(format!("Unknown option: {}", arg)),
+                _ if path.is_none() => path = Some(arg),
+                _ => return Err(format!("Unexpected argument: {}", arg)),
+            }
+        }
+
+        if let Some(d) = depth {
+            if !(1..=32).contains(&d) {
+                return Err(String::from("--depth must be between 1 and 32"));
+            }
+        }
+
+        if threads == 0 {
+            return Err(String::from("--threads must be at least 1"));
+        }
+
+        Ok(AnalyzeConfig {
+            path: path.ok_or_else(|| String::from("No state file given"))?,
+            depth: depth.map(|d| d as u8),
+            // A day is as good as no limit.
+            move_time: move_time.unwrap_or(if depth.is_some() { 86400000 } else { 800 }),
+            threads: threads,
+            search: search,
+            quiescence: quiescence,
+            weights: weights,
+            model: model,
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BenchConfig {
+    pub depth: u8,
+    pub move_time: Option<i64>,
+    pub search: String,
+    pub quiescence: u8,
+    pub weights: Option<String>,
+    pub model: Option<String>,
+    pub ordering: MoveOrdering,
+    pub reductions: bool,
+    pub extensions: bool,
+    pub null_moves: bool,
+}
+
+impl BenchConfig {
+    pub fn from_args<I: Iterator<Item = String>>(mut args: I) -> Result<BenchConfig, String> {
+        let mut config = BenchConfig {
+            depth: 7,
+            move_time: None,
+            search: String::from("mtdf"),
+            quiescence: 4,
+            weights: None,
+            model: None,
+            ordering: MoveOrdering::default(),
+            reductions: true,
+            extensions: true,
+            null_moves: false,
+        };
+
+        while let Some(arg) = args.next() {
+            match arg.as_ref() {
+                "--depth" => {
+                    let depth = parse_number(&mut args, &arg)?;
+                    if !(1..=32).contains(&depth) {
+                        return Err(String::from("--depth must be between 1 and 32"));
+                    }
+                    config.depth = depth as u8;
+                }
+                "--null-moves" => config.null_moves = true,
+                "--time" => config.move_time = Some(parse_number(&mut args, &arg)? as i64),
+                "--search" => config.search = parse_search(&mut args, &arg)?,
+                "--quiescence" => config.quiescence = parse_plies(&mut args, &arg)?,
+                "--weights" => config.weights = Some(next_value(&mut args, &arg)?),
+                "--model" => config.model = Some(next_value(&mut args, &arg)?),
+                "--without" => {
+                    for name in next_value(&mut args, &arg)?.split(',') {
+                        match name {
+                            "killers" => config.ordering.killers = false,
+                            "counter-moves" => config.ordering.counter_moves = false,
+                            "history" => config.ordering.history = false,
+                            "reductions" => config.reductions = false,
+                            "extensions" => config.extensions = false,
+                            _ => return Err(format!("Unknown search feature: {}", name)),
+                        }
+                    }
+                }
+                _ => return Err(format!("Unknown option: {}", arg)),
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct TuneConfig {
+    pub iterations: usize,
+    pub games: usize,
+    pub turns: usize,
+    pub move_time: i64,
+    pub seed: Option<u64>,
+    pub weights: Option<String>,
+    pub out: String,
+    pub verify_games: usize,
+}
+
+impl TuneConfig {
+    pub fn from_args<I: Iterator<Item = String>>(mut args: I) -> Result<TuneConfig, String> {
+        let mut config = TuneConfig {
+            iterations: 100,
+            games: 2,
+            turns: 100,
+            move_time: 20,
+            seed: None,
+            weights: None,
+            out: String::from("tuned.json"),
+            verify_games: 20,
+        };
+
+        while let Some(arg) = args.next() {
+            match arg.as_ref() {
+                "--iterations" => config.iterations = parse_number(&mut args, &arg)?,
+                "--games" => config.games = parse_number(&mut args, &arg)?,
+                "--turns" => config.turns = parse_number(&mut args, &arg)?,
+                "--move-time" => config.move_time = parse_number(&mut args, &arg)? as i64,
+                "--seed" => config.seed = Some(parse_number(&mut args, &arg)? as u64),
+                "--weights" => config.weights = Some(next_value(&mut args, &arg)?),
+                "--out" => config.out = next_value(&mut args, &arg)?,
+                "--verify-games" => config.verify_games = parse_number(&mut args, &arg)?,
+                _ => return Err(format!("Unknown option: {}", arg)),
+            }
+        }
+
+        if config.games == 0 || config.turns == 0 {
+            return Err(String::from("--games and --turns must be at least 1"));
+        }
+
+        if !config.games.is_multiple_of(2) || !config.verify_games.is_multiple_of(2) {
+            return Err(String::from("--games and --verify-games must be even"));
+        }
+
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec;
+
+    use super::{AnalyzeConfig, BenchConfig, Config, MapgenConfig, MatchConfig, Mode,
+                ReplayConfig, ServerConfig, TuneConfig};
+
+    fn args(line: &str) -> vec::IntoIter<String> {
+        line.split_whitespace().map(String::from).collect::<Vec<_>>().into_iter()
+    }
+
+    #[test]
+    fn client_defaults() {
+        let c = Config::from_args(args("--key abc")).unwrap();
+        assert_eq!(c.server, "http://vindinium.org");
+        assert_eq!(c.key, "abc");
+        assert_eq!(c.mode, Mode::Arena);
+        assert_eq!(c.games, 1);
+        assert_eq!(c.timeout, 30);
+        assert_eq!(c.retries, 5);
+        assert_eq!(c.move_timeout, 1000);
+        assert!(c.ponder);
+        assert_eq!(c.threads, 1);
+        assert_eq!(c.search, "mtdf");
+        assert_eq!(c.start_url(), "http://vindinium.org/api/arena");
+        assert_eq!(c.start_body(), "key=abc");
+    }
+
+    #[test]
+    fn training_options() {
+        let c = Config::from_args(args("--key abc --server http://localhost:9000/ --training \
+                                        --turns 50 --map m3"))
+            .unwrap();
+        assert_eq!(c.start_url(), "http://localhost:9000/api/training");
+        assert_eq!(c.start_body(), "key=abc&turns=50&map=m3");
+
+        // Only the official server's maps are known.
+        assert!(Config::from_args(args("--key abc --training --map m7")).is_err());
+        assert!(Config::from_args(args("--key abc --server http://localhost:9000 --training \
+                                        --map m7"))
+            .is_ok());
+    }
+
+    #[test]
+    fn invalid_client_options() {
+        assert_eq!(Config::from_args(args("")).unwrap_err(),
+                   "No API key given, use --key or --key-file");
+        assert_eq!(Config::from_args(args("--key abc --turns 50")).unwrap_err(),
+                   "--turns and --map are only valid with --training");
+        assert_eq!(Config::from_args(args("--key abc --training --turns 0")).unwrap_err(),
+                   "--turns must be at least 1");
+        assert_eq!(Config::from_args(args("--key abc --threads 0")).unwrap_err(),
+                   "--threads must be at least 1");
+        assert_eq!(Config::from_args(args("--key abc --games ten")).unwrap_err(),
+                   "Invalid value for --games: ten");
+        assert_eq!(Config::from_args(args("--key abc --games")).unwrap_err(),
+                   "Missing value for --games");
+        assert!(Config::from_args(args("--key abc --search minimax"))
+            .unwrap_err()
+            .starts_with("Unknown search algorithm: minimax"));
+        assert_eq!(Config::from_args(args("--key abc --quiescence 33")).unwrap_err(),
+                   "--quiescence must be at most 32");
+    }
+
+    #[test]
+    fn unknown_options_are_rejected() {
+        let unknown = String::from("Unknown option: --verbose");
+        assert_eq!(Config::from_args(args("--key abc --verbose")).unwrap_err(), unknown);
+        assert_eq!(ServerConfig::from_args(args("--verbose")).unwrap_err(), unknown);
+        assert_eq!(MatchConfig::from_args(args("--verbose")).unwrap_err(), unknown);
+        assert_eq!(MapgenConfig::from_args(args("--verbose")).unwrap_err(), unknown);
+        assert_eq!(ReplayConfig::from_args(args("game.jsonl --verbose")).unwrap_err(), unknown);
+        assert_eq!(AnalyzeConfig::from_args(args("state.json --verbose")).unwrap_err(),
+                   unknown);
+        assert_eq!(BenchConfig::from_args(args("--verbose")).unwrap_err(), unknown);
+        assert_eq!(TuneConfig::from_args(args("--verbose")).unwrap_err(), unknown);
+    }
+
+    #[test]
+    fn server_options() {
+        let c = ServerConfig::from_args(args("")).unwrap();
+        assert_eq!((c.address.as_ref(), c.maps.as_ref(), c.move_timeout),
+                   ("127.0.0.1:9000", "maps", 1000));
+
+        let c = ServerConfig::from_args(args("--address 0.0.0.0:9123 --move-timeout 0")).unwrap();
+        assert_eq!((c.address.as_ref(), c.move_timeout), ("0.0.0.0:9123", 0));
+
+        // --timeout is the client's network timeout, in seconds.
+        assert!(ServerConfig::from_args(args("--timeout 1000")).is_err());
+    }
+
+    #[test]
+    fn match_options() {
+        let c = MatchConfig::from_args(args("")).unwrap();
+        assert_eq!(c.players, vec!["beagle", "random", "random", "random"]);
+        assert_eq!((c.games, c.turns, c.move_time), (10, 300, 100));
+
+        let c = MatchConfig::from_args(args("--players mcts,beagle,greedy,random --seed 42 \
+                                             --size 20"))
+            .unwrap();
+        assert_eq!(c.players, vec!["mcts", "beagle", "greedy", "random"]);
+        assert_eq!((c.seed, c.size), (Some(42), Some(20)));
+
+        assert_eq!(MatchConfig::from_args(args("--players beagle,random")).unwrap_err(),
+                   "Expected four players, got 2");
+        assert_eq!(MatchConfig::from_args(args("--turns 0")).unwrap_err(),
+                   "--turns must be at least 1");
+    }
+
+    #[test]
+    fn map_sizes_are_even_and_in_range() {
+        assert_eq!(MapgenConfig::from_args(args("--size 18")).unwrap().size, Some(18));
+        assert!(MapgenConfig::from_args(args("--size 19")).is_err());
+        assert!(MapgenConfig::from_args(args("--size 8")).is_err());
+        assert!(MapgenConfig::from_args(args("--size 30")).is_err());
+    }
+
+    #[test]
+    fn replay_and_analyze_need_a_file() {
+        assert_eq!(ReplayConfig::from_args(args("")).unwrap_err(), "No replay file given");
+        assert_eq!(ReplayConfig::from_args(args("a.jsonl b.jsonl")).unwrap_err(),
+                   "Unexpected argument: b.jsonl");
+        assert_eq!(AnalyzeConfig::from_args(args("--depth 5")).unwrap_err(),
+                   "No state file given");
+    }
+
+    #[test]
+    fn analyze_thinks_without_a_limit_at_a_fixed_depth() {
+        let c = AnalyzeConfig::from_args(args("state.json")).unwrap();
+        assert_eq!((c.depth, c.move_time), (None, 800));
+
+        let c = AnalyzeConfig::from_args(args("state.json --depth 10")).unwrap();
+        assert_eq!((c.depth, c.move_time), (Some(10), 86400000));
+
+        assert!(AnalyzeConfig::from_args(args("state.json --depth 0")).is_err());
+        assert!(AnalyzeConfig::from_args(args("state.json --depth 33")).is_err());
+    }
+
+    #[test]
+    fn bench_features_can_be_turned_off() {
+        let c = BenchConfig::from_args(args("")).unwrap();
+        assert_eq!(c.depth, 7);
+        assert!(c.ordering.killers && c.ordering.counter_moves && c.ordering.history);
+        assert!(c.reductions && c.extensions && !c.null_moves);
+
+        let c = BenchConfig::from_args(args("--without killers,reductions --null-moves")).unwrap();
+        assert!(!c.ordering.killers && c.ordering.counter_moves && c.ordering.history);
+        assert!(!c.reductions && c.extensions && c.null_moves);
+
+        assert_eq!(BenchConfig::from_args(args("--without pruning")).unwrap_err(),
+                   "Unknown search feature: pruning");
+        assert!(BenchConfig::from_args(args("--depth 0")).is_err());
+    }
+
+    #[test]
+    fn tune_games_must_be_even() {
+        let c = TuneConfig::from_args(args("")).unwrap();
+        assert_eq!((c.iterations, c.games, c.verify_games), (100, 2, 20));
+
+        assert!(TuneConfig::from_args(args("--games 3")).is_err());
+        assert!(TuneConfig::from_args(args("--verify-games 5")).is_err());
+        assert!(TuneConfig::from_args(args("--games 0")).is_err());
+    }
+}
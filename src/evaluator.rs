@@ -0,0 +1,240 @@
+use std::fs::File;
+use std::io::Read;
+
+use serde_json;
+use serde_json::Value;
+
+use bot::Bot;
+use replay;
+use runner;
+use state::State;
+
+/// Scores positions for the search. Like search strategies, evaluators are
+/// shared between the threads of a `Bot` and can use its state, such as the
+/// rating changes worked out at the start of a game.
+pub trait Evaluator: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// The score of `s` for `s.hero`. Higher is better; one point is worth
+    /// about one gold.
+    fn eval(&self, bot: &Bot, s: &mut State) -> i32;
+}
+
+/// The evaluation Beagle was written with, using the weights of its `Bot`.
+pub struct Handcrafted;
+
+impl Evaluator for Handcrafted {
+    fn name(&self) -> &'static str {
+        "handcrafted"
+    }
+
+    fn eval(&self, bot: &Bot, s: &mut State) -> i32 {
+        bot.handcrafted_eval(s)
+    }
+}
+
+pub const NUM_FEATURES: usize = 22;
+
+/// What `features` computes, in order. Heroes are numbered from ours (0)
+/// in the order they move, and distances are in steps, 255 if there is no
+/// way there.
+pub const FEATURE_NAMES: [&'static str; NUM_FEATURES] = ["turns_left",
+                                                         "gold0",
+                                                         "mines0",
+                                                         "life0",
+                                                         "projected0",
+                                                         "gold1",
+                                                         "mines1",
+                                                         "life1",
+                                                         "projected1",
+                                                         "gold2",
+                                                         "mines2",
+                                                         "life2",
+                                                         "projected2",
+                                                         "gold3",
+                                                         "mines3",
+                                                         "life3",
+                                                         "projected3",
+                                                         "tavern_dist",
+                                                         "mine_dist",
+                                                         "enemy_dist1",
+                                                         "enemy_dist2",
+                                                         "enemy_dist3"];
+
+/// Describes `s` from the point of view of `s.hero`, for a `Model`.
+/// The projected gold of a hero is what it ends with if nobody takes a mine
+/// anymore.
+pub fn features(s: &mut State) -> [f64; NUM_FEATURES] {
+    let mut f = [0f64; NUM_FEATURES];
+    let turns_left = (s.game.max_turns - s.game.turn) / 4;
+    let hero = s.hero.clone();
+    f[0] = turns_left as f64;
+
+    for i in 0..4 {
+        let h = &s.game.heroes[(hero.id - 1 + i) % 4];
+        f[1 + 4 * i] = h.gold as f64;
+        f[2 + 4 * i] = h.mine_count as f64;
+        f[3 + 4 * i] = h.life as f64;
+        f[4 + 4 * i] = (h.gold as usize + h.mine_count as usize * turns_left) as f64;
+    }
+
+    let (tdist, _) = s.game.board.get_closest_tavern(&hero.pos);
+    let (mdist, _) = s.game.board.get_closest_mine(&hero.pos, hero.id);
+    f[17] = tdist as f64;
+    f[18] = mdist as f64;
+
+    for i in 1..4 {
+        let pos = s.game.heroes[(hero.id - 1 + i) % 4].pos;
+        f[18 + i] = s.game.board.shortest_path_length(&hero.pos, &pos) as f64;
+    }
+
+    f
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Layer {
+    /// One row of input weights per output.
+    weights: Vec<Vec<f64>>,
+    biases: Vec<f64>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ModelFile {
+    layers: Vec<Layer>,
+    #[serde(default="one")]
+    scale: f64,
+}
+
+fn one() -> f64 {
+    1.0
+}
+
+/// A linear model or a small multi-layer perceptron over `features`, read
+/// from a JSON file such as
+///
+/// ```json
+/// {"layers": [{"weights": [[1.0, 0.5, ...]], "biases": [0.0]}], "scale": 1.0}
+/// ```
+///
+/// Every layer but the last is followed by a ReLU, and the last has a single
+/// output, which is multiplied by `scale` to give the score. One layer makes
+/// a linear model. Features are not normalized; a trainer that normalizes
+/// them has to fold that into the first layer.
+pub struct Model {
+    layers: Vec<Layer>,
+    scale: f64,
+}
+
+impl Model {
+    pub fn load(path: &str) -> Result<Model, String> {
+        let mut text = String::new();
+        File::open(path)
+            .and_then(|mut f| f.read_to_string(&mut text))
+            .map_err(|e| format!("Could not read {}: {}", path, e))?;
+
+        let file: ModelFile = serde_json::from_str(&text).map_err(|e| format!("{}: {}", path, e))?;
+
+        if file.layers.is_empty() {
+            return Err(format!("{}: a model needs at least one layer", path));
+        }
+
+        let mut inputs = NUM_FEATURES;
+        for (i, layer) in file.layers.iter().enumerate() {
+            if layer.weights.is_empty() || layer.weights.len() != layer.biases.len() {
+                return Err(format!("{}: layer {} needs as many biases as rows of weights",
+                                   path,
+                                   i + 1));
+            }
+            if layer.weights.iter().any(|row| row.len() != inputs) {
+                return Err(format!("{}: layer {} needs {} weights per row", path, i + 1, inputs));
+            }
+            inputs = layer.biases.len();
+        }
+
+        if inputs != 1 {
+            return Err(format!("{}: the last layer needs a single output", path));
+        }
+
+        Ok(Model {
+            layers: file.layers,
+            scale: file.scale,
+        })
+    }
+
+    /// Runs the model on a feature vector.
+    pub fn predict(&self, features: &[f64]) -> f64 {
+        let mut input = features.to_vec();
+
+        for (i, layer) in self.layers.iter().enumerate() {
+            let hidden = i + 1 < self.layers.len();
+            input = layer.weights
+                .iter()
+                .zip(layer.biases.iter())
+                .map(|(row, b)| {
+                    let x = row.iter().zip(input.iter()).fold(*b, |sum, (w, x)| sum + w * x);
+                    if hidden { x.max(0.0) } else { x }
+                })
+                .collect();
+        }
+
+        input[0] * self.scale
+    }
+}
+
+impl Evaluator for Model {
+    fn name(&self) -> &'static str {
+        "model"
+    }
+
+    fn eval(&self, _bot: &Bot, s: &mut State) -> i32 {
+        let x = self.predict(&features(s));
+        x.max(i32::min_value() as f64 / 2.0).min(i32::max_value() as f64 / 2.0) as i32
+    }
+}
+
+/// Whether the JSON file at `path` holds a model rather than weights.
+pub fn is_model_file(path: &str) -> Result<bool, String> {
+    let mut text = String::new();
+    File::open(path)
+        .and_then(|mut f| f.read_to_string(&mut text))
+        .map_err(|e| format!("Could not read {}: {}", path, e))?;
+
+    let value: Value = serde_json::from_str(&text).map_err(|e| format!("{}: {}", path, e))?;
+    Ok(value.get("layers").is_some())
+}
+
+/// Prints the features of every state our hero had to move in, in the
+/// replays at `paths`, as CSV. Each row ends with the gold and the rank our
+/// hero finished the game with, which a model can be trained to predict.
+/// Replays of games that were cut short are skipped, since they don't say
+/// how the game ended.
+pub fn export_features(paths: &[String]) -> Result<(), String> {
+    if paths.is_empty() {
+        return Err(String::from("No replay files given"));
+    }
+
+    println!("{},final_gold,final_rank", FEATURE_NAMES.join(","));
+
+    for path in paths {
+        let mut turns = replay::load(path)?;
+        let (gold, rank) = match turns.last() {
+            Some(last) if last.state.game.finished => {
+                let idx = last.state.hero.id - 1;
+                (last.state.game.heroes[idx].gold, runner::ranks(&last.state)[idx])
+            }
+            _ => {
+                eprintln!("Skipping {}: the game didn't finish", path);
+                continue;
+            }
+        };
+
+        for turn in turns.iter_mut().filter(|t| t.mv.is_some()) {
+            let f: Vec<String> = features(&mut turn.state).iter().map(|x| x.to_string()).collect();
+            println!("{},{},{}", f.join(","), gold, rank);
+        }
+    }
+
+    Ok(())
+}
@@ -6,7 +6,7 @@ use fnv::FnvHasher;
 use board::Board;
 use hero::Hero;
 
-#[derive(Deserialize, Debug, Eq)]
+#[derive(Serialize, Deserialize, Debug, Eq)]
 pub struct Game {
     pub id: String,
     pub turn: usize,
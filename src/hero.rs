@@ -5,7 +5,7 @@ use fnv::FnvHasher;
 
 use position::Position;
 
-#[derive(Clone, Deserialize, Debug, Eq)]
+#[derive(Clone, Serialize, Deserialize, Debug, Eq)]
 pub struct Hero {
     pub id: usize,
     pub name: String,
@@ -50,3 +50,27 @@ impl hash::Hash for Hero {
         self.crashed.hash(state);
     }
 }
+
+impl Hero {
+    pub fn new(id: usize, name: &str, pos: Position) -> Hero {
+        Hero {
+            id: id,
+            name: String::from(name),
+            user_id: String::default(),
+            elo: 0,
+            pos: pos,
+            last_dir: String::default(),
+            life: 100,
+            gold: 0,
+            mine_count: 0,
+            spawn_pos: pos,
+            crashed: false,
+        }
+    }
+
+    // Training games don't report Elo ratings, so assume everyone is at the
+    // starting rating to keep rank-based calculations meaningful.
+    pub fn rating(&self) -> u16 {
+        if self.elo == 0 { 1200 } else { self.elo }
+    }
+}
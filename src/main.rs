@@ -2,6 +2,7 @@ extern crate time;
 extern crate rand;
 extern crate hyper;
 extern crate fnv;
+extern crate serde;
 
 #[macro_use]
 extern crate serde_derive;
@@ -20,88 +21,339 @@ mod tile;
 mod bot;
 mod transposition_table;
 mod zobrist;
-mod lru;
+mod config;
+mod summary;
+mod client;
+mod server;
+mod policy;
+mod maps;
+mod runner;
+mod mapgen;
+mod transitions;
+mod replay;
+mod viewer;
+mod analyze;
+mod timing;
+mod search;
+mod mcts;
+mod ordering;
+mod bench;
+mod weights;
+mod tune;
+mod evaluator;
 
 use direction::Direction;
-use std::io::Read;
+use config::{AnalyzeConfig, BenchConfig, Config, MapgenConfig, MatchConfig, ReplayConfig,
+             ServerConfig, TuneConfig};
+use summary::{GameSummary, Totals};
+use rand::Rng;
+use std::env;
+use client::{Connection, GameEnd};
+use transitions::Recorder;
+use replay::ReplayWriter;
+use timing::TimeManager;
+use weights::Weights;
+use evaluator::Model;
+use std::process;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::Duration;
 // use std::hash::{Hash, Hasher, SipHasher};
-use hyper::client::*;
-use hyper::header::ContentType;
-use hyper::Url;
 
 fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.iter().any(|a| a == "-h" || a == "--help") {
+        println!("{}", config::USAGE);
+        return;
+    }
+
     unsafe {
         zobrist::ZOBRIST = zobrist::ZobristTable::default();
     }
+
+    match args.first().map(|a| a.as_ref()) {
+        Some("serve") => {
+            server::serve(parse_or_exit(ServerConfig::from_args(args.into_iter().skip(1))))
+        }
+        Some("match") => {
+            runner::run(&parse_or_exit(MatchConfig::from_args(args.into_iter().skip(1))))
+        }
+        Some("mapgen") => mapgen(parse_or_exit(MapgenConfig::from_args(args.into_iter().skip(1)))),
+        Some("verify") => {
+            if !transitions::verify(&args[1..]) {
+                process::exit(1);
+            }
+        }
+        Some("replay") => {
+            let config = parse_or_exit(ReplayConfig::from_args(args.into_iter().skip(1)));
+            if let Err(e) = viewer::run(&config.path, config.turn) {
+                println!("{}", e);
+                process::exit(1);
+            }
+        }
+        Some("analyze") => {
+            let config = parse_or_exit(AnalyzeConfig::from_args(args.into_iter().skip(1)));
+            if let Err(e) = analyze::run(&config) {
+                println!("{}", e);
+                process::exit(1);
+            }
+        }
+        Some("bench") => {
+            let config = parse_or_exit(BenchConfig::from_args(args.into_iter().skip(1)));
+            if let Err(e) = bench::run(&config) {
+                println!("{}", e);
+                process::exit(1);
+            }
+        }
+        Some("features") => {
+            if let Err(e) = evaluator::export_features(&args[1..]) {
+                println!("{}", e);
+                process::exit(1);
+            }
+        }
+        Some("tune") => {
+            let config = parse_or_exit(TuneConfig::from_args(args.into_iter().skip(1)));
+            if let Err(e) = tune::run(&config) {
+                println!("{}", e);
+                process::exit(1);
+            }
+        }
+        _ => play(parse_or_exit(Config::from_args(args.into_iter()))),
+    }
+}
+
+fn parse_or_exit<T>(config: Result<T, String>) -> T {
+    match config {
+        Ok(c) => c,
+        Err(msg) => {
+            println!("{}\n\n{}", msg, config::USAGE);
+            process::exit(1);
+        }
+    }
+}
+
+fn mapgen(config: MapgenConfig) {
+    let seed = config.seed.unwrap_or_else(|| rand::thread_rng().gen());
+    let size = config.size.unwrap_or_else(|| mapgen::size_for_seed(seed));
+
+    eprintln!("size {}, seed {}", size, seed);
+    print!("{}", mapgen::generate(size, seed));
+}
+
+fn play(config: Config) {
+    let conn = Connection::new(&config);
+    let mut recorder = config.transitions.as_ref().map(|path| parse_or_exit(Recorder::open(path)));
     let mut bot = bot::Bot::new();
-    let client = Client::new();
-    let mut res = client
-        .post("http://vindinium.org/api/arena")
-        .header(ContentType("application/x-www-form-urlencoded".parse().unwrap()))
-        .body("key=eqwxqpa8")
-        .send()
-        .unwrap();
-    let mut body: String = "".to_owned();
-
-    if res.status != hyper::Ok {
-        println!("{:?}", res.status_raw());
-        return;
+    bot.set_threads(config.threads);
+    bot.set_strategy(search::from_name(&config.search).unwrap());
+    bot.set_quiescence_plies(config.quiescence);
+    if let Some(ref path) = config.weights {
+        bot.set_weights(parse_or_exit(Weights::load(path)));
+    }
+    if let Some(ref path) = config.model {
+        bot.set_evaluator(Arc::new(parse_or_exit(Model::load(path))));
+    }
+    let mut timer = TimeManager::new(config.move_timeout);
+    let mut totals = Totals::default();
+    let mut game = 0;
+
+    while config.games == 0 || game < config.games {
+        game += 1;
+        bot.new_game();
+
+        match play_game(&config, &conn, &mut bot, &mut timer, &mut recorder) {
+            GameEnd::Finished(state) => {
+                let summary = GameSummary::from_state(&state);
+                totals.add(&summary);
+                println!("Game {}: {}", game, summary);
+            }
+            GameEnd::Unconfirmed(state) => {
+                // Where the game stood last isn't how it ended, so it doesn't
+                // count towards ranks and gold.
+                totals.failed += 1;
+                println!("Game {}: over before the final state arrived, last seen at turn {}: {}",
+                         game,
+                         state.game.turn,
+                         GameSummary::from_state(&state));
+            }
+            GameEnd::Crashed(msg) => {
+                totals.crashed += 1;
+                println!("Game {}: crashed, {}", game, msg);
+            }
+            GameEnd::Failed(e) => {
+                totals.failed += 1;
+                println!("Game {}: failed, {}", game, e);
+
+                // Give an unreachable server some time to come back before
+                // queueing up for the next game.
+                if let client::Error::Unreachable(_) = e {
+                    thread::sleep(Duration::from_secs(10));
+                }
+            }
+        }
+
+        println!("Total: {}", totals);
     }
+}
 
-    res.read_to_string(&mut body).ok();
+/// Ends a replay with the last state the server sent. Unless that state is
+/// finished, the replay doesn't show how the game ended.
+fn end_replay(replay: &mut Option<ReplayWriter>, state: &state::State) {
+    if let Some(ref mut w) = *replay {
+        w.finish(state);
+    }
+}
 
-    let mut state: state::State = serde_json::from_str(&body).unwrap();
+fn play_game(config: &Config,
+             conn: &Connection,
+             bot: &mut bot::Bot,
+             timer: &mut TimeManager,
+             recorder: &mut Option<Recorder>)
+             -> GameEnd {
+    let mut state = match conn.start(config) {
+        Ok(s) => s,
+        Err(e) => return GameEnd::Failed(e),
+    };
     state.game.board.initialize();
+    println!("{}", state.view_url);
+
+    let mut last_server_state = state.clone();
+    // Set when a move may or may not have reached the server. The same move
+    // is sent again, and the next state the server sends is taken as it is,
+    // since we don't know whether it counted once or twice.
+    let mut resync = false;
+    let mut resend = None;
+    let mut failures = 0;
 
-    let mut new_state: state::State;
+    let mut replay = config.replay_dir.as_ref().and_then(|dir| {
+        match ReplayWriter::create(dir, &state.game.id) {
+            Ok(w) => Some(w),
+            Err(e) => {
+                println!("{}", e);
+                None
+            }
+        }
+    });
 
     loop {
-        let mv = bot.choose_move(&mut state);
-        println!("{}: {}", state.game.turn, mv);
+        let mv = match resend.take() {
+            Some(mv) => mv,
+            None => {
+                let budget = timer.budget(&mut state);
+                bot.set_move_time(budget);
+                let mv = bot.choose_move(&mut state);
+                println!("{}: {}", state.game.turn, mv);
+                mv
+            }
+        };
+
+        let sent = time::get_time();
+        let predicted = if config.ponder {
+            Some(bot.predicted_state(&state, mv))
+        } else {
+            None
+        };
+
+        let result = match predicted {
+            Some(mut predicted) if !predicted.game.finished => {
+                // Search the most likely next position until the server answers.
+                let stop = bot.stop_flag();
+                stop.store(false, Ordering::Relaxed);
+                thread::scope(|scope| {
+                    let request = scope.spawn(|| {
+                        let result = conn.play(&state, &config.key, mv);
+                        stop.store(true, Ordering::Relaxed);
+                        result
+                    });
 
-        res = client
-            .post(Url::parse(&state.play_url).unwrap())
-            .header(ContentType("application/x-www-form-urlencoded".parse().unwrap()))
-            .body(&(String::from("key=eqwxqpa8&dir=") + mv.into()))
-            .send()
-            .unwrap();
+                    bot.ponder(&mut predicted);
+                    request.join().unwrap()
+                })
+            }
+            _ => conn.play(&state, &config.key, mv),
+        };
 
-        if res.status != hyper::Ok {
-            println!("{:?}", res.status_raw());
-            return;
+        let mut new_state = match result {
+            Ok(s) => {
+                timer.record_round_trip((time::get_time() - sent).num_milliseconds());
+                failures = 0;
+                s
+            }
+            Err(ref e) if e.is_game_over() => {
+                end_replay(&mut replay, &last_server_state);
+                return GameEnd::Unconfirmed(last_server_state);
+            }
+            Err(ref e) if e.is_crash() => {
+                end_replay(&mut replay, &last_server_state);
+                return GameEnd::Crashed(format!("{}", e));
+            }
+            Err(ref e) if e.is_transient() && failures < config.retries => {
+                println!("{}, sending {} again and resyncing with the server's answer", e, mv);
+                failures += 1;
+                resync = true;
+                resend = Some(mv);
+                continue;
+            }
+            Err(e) => {
+                end_replay(&mut replay, &last_server_state);
+                return GameEnd::Failed(e);
+            }
+        };
+        new_state.game.board.initialize();
+
+        // Only moves the server took go into the replay. A resent move is
+        // the one chosen for this state, so the search still describes it.
+        if let Some(ref mut w) = replay {
+            w.record(&last_server_state, Some(mv), Some(bot.last_search()));
         }
 
-        body = String::default();
-        res.read_to_string(&mut body).ok();
-        new_state = serde_json::from_str(&body).unwrap();
+        let h_idx = state.game.turn % 4;
+        let mut moves = vec![mv];
+        for i in 1..4 {
+            moves.push(Direction::from(new_state.game.heroes[(h_idx + i) % 4].last_dir.clone()));
+        }
+
+        match *recorder {
+            Some(ref mut r) if !resync => {
+                // The last response of a game can come before all heroes moved.
+                let played = new_state.game.turn - last_server_state.game.turn;
+                r.record(&last_server_state, &moves[..played.min(4)], &new_state);
+            }
+            _ => {}
+        }
+        last_server_state = new_state.clone();
 
         if new_state.game.finished {
-            break;
+            end_replay(&mut replay, &new_state);
+            return GameEnd::Finished(new_state);
+        }
+
+        if new_state.hero.crashed {
+            end_replay(&mut replay, &new_state);
+            return GameEnd::Crashed(String::from("hero crashed on the server"));
         }
 
-        if state.game.heroes[0].crashed != new_state.game.heroes[0].crashed ||
+        if resync || state.game.heroes[0].crashed != new_state.game.heroes[0].crashed ||
            state.game.heroes[1].crashed != new_state.game.heroes[1].crashed ||
            state.game.heroes[2].crashed != new_state.game.heroes[2].crashed ||
            state.game.heroes[3].crashed != new_state.game.heroes[3].crashed {
 
-            state = new_state.clone();
-            state.game.board.initialize();
+            state = new_state;
+            resync = false;
         } else {
-            state.make_move(mv);
-            let h_idx = new_state.game.turn % 4;
-            for i in 1..4 {
-                let nextmv = &new_state.game.heroes[(h_idx + i) % 4].last_dir;
-                match nextmv.as_ref() {
-                    "North" => state.make_move(Direction::North),
-                    "East" => state.make_move(Direction::East),
-                    "South" => state.make_move(Direction::South),
-                    "West" => state.make_move(Direction::West),
-                    "Stay" => state.make_move(Direction::Stay),
-                    _ => unreachable!(),
-                };
+            for dir in &moves {
+                state.make_move(*dir);
+            }
+
+            let diffs = transitions::differences(&state, &new_state);
+            if !diffs.is_empty() {
+                println!("Simulation diverged from the server:");
+                for d in diffs {
+                    println!("    {}", d);
+                }
+                state = new_state;
             }
         }
     }
-
-    println!("{}", state.view_url);
 }
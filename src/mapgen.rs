@@ -0,0 +1,152 @@
+use std::collections::VecDeque;
+
+use rand::{Rng, SeedableRng, StdRng};
+
+use board::Board;
+use tile::Tile;
+
+pub const MIN_SIZE: usize = 10;
+pub const MAX_SIZE: usize = 28;
+
+/// Picks an even map size between MIN_SIZE and MAX_SIZE, so that a seed
+/// alone is enough to reproduce a map.
+pub fn size_for_seed(seed: u64) -> usize {
+    MIN_SIZE + 2 * (seed % ((MAX_SIZE - MIN_SIZE) / 2 + 1) as u64) as usize
+}
+
+/// Generates a Vindinium-style map: one quadrant is filled at random and then
+/// mirrored both ways, so every spawn point sees the same surroundings. Each
+/// quadrant has a tavern, at least one mine and a spawn point, and everything
+/// is reachable from every spawn point.
+pub fn generate(size: usize, seed: u64) -> Board {
+    assert!(size >= MIN_SIZE && size <= MAX_SIZE && size % 2 == 0,
+            "Map size must be even and between {} and {}, got {}",
+            MIN_SIZE,
+            MAX_SIZE,
+            size);
+
+    let seed = [seed as usize, (seed >> 32) as usize];
+    let mut rng: StdRng = SeedableRng::from_seed(&seed[..]);
+
+    loop {
+        if let Some(tiles) = try_generate(size, &mut rng) {
+            let mut s = String::with_capacity(2 * size * size);
+            for t in &tiles {
+                s.push_str(&format!("{}", t));
+            }
+
+            return Board::from_tiles(size as i8, s);
+        }
+    }
+}
+
+fn try_generate(size: usize, rng: &mut StdRng) -> Option<Vec<Tile>> {
+    let half = size / 2;
+    let wall_p = rng.gen_range(0.1, 0.35);
+    let mine_p = rng.gen_range(0.03, 0.08);
+
+    let mut quad = vec![Tile::Air; half * half];
+    for t in quad.iter_mut() {
+        let r = rng.gen::<f64>();
+        if r < wall_p {
+            *t = Tile::Wall;
+        } else if r < wall_p + mine_p {
+            *t = Tile::Mine(0);
+        }
+    }
+
+    let tavern = rng.gen_range(0, half * half);
+    quad[tavern] = Tile::Tavern;
+
+    // Keep the spawn points away from the mirror axes, or heroes would start
+    // the game right next to each other.
+    let mut spawn = tavern;
+    while spawn == tavern {
+        spawn = rng.gen_range(0, half - 2) * half + rng.gen_range(0, half - 2);
+    }
+    quad[spawn] = Tile::Hero(0);
+
+    // Mirror the quadrant. Heroes are numbered clockwise from the top left.
+    let mut tiles = vec![Tile::Wall; size * size];
+    for x in 0..size {
+        for y in 0..size {
+            let qx = if x < half { x } else { size - 1 - x };
+            let qy = if y < half { y } else { size - 1 - y };
+
+            tiles[x * size + y] = match quad[qx * half + qy] {
+                Tile::Hero(_) => {
+                    Tile::Hero(match (x < half, y < half) {
+                        (true, true) => 1,
+                        (true, false) => 2,
+                        (false, false) => 3,
+                        (false, true) => 4,
+                    })
+                }
+                t => t,
+            };
+        }
+    }
+
+    let reached = reachable(&tiles, size, (spawn / half) * size + spawn % half);
+
+    for (i, t) in tiles.iter_mut().enumerate() {
+        match *t {
+            Tile::Air if !reached[i] => *t = Tile::Wall,
+            Tile::Hero(_) if !reached[i] => return None,
+            Tile::Tavern if !reached[i] => return None,
+            Tile::Mine(_) if !reached[i] => *t = Tile::Wall,
+            _ => (),
+        }
+    }
+
+    let mines = tiles.iter().filter(|t| **t == Tile::Mine(0)).count();
+    if mines < 4 {
+        return None;
+    }
+
+    Some(tiles)
+}
+
+/// Marks the tiles a hero starting at `start` can walk on or interact with.
+fn reachable(tiles: &[Tile], size: usize, start: usize) -> Vec<bool> {
+    let mut reached = vec![false; tiles.len()];
+    let mut q = VecDeque::new();
+
+    reached[start] = true;
+    q.push_back(start);
+
+    while let Some(cur) = q.pop_front() {
+        let (x, y) = (cur / size, cur % size);
+        let mut nb = Vec::with_capacity(4);
+
+        if x > 0 {
+            nb.push(cur - size);
+        }
+        if x + 1 < size {
+            nb.push(cur + size);
+        }
+        if y > 0 {
+            nb.push(cur - 1);
+        }
+        if y + 1 < size {
+            nb.push(cur + 1);
+        }
+
+        for n in nb {
+            if reached[n] {
+                continue;
+            }
+
+            match tiles[n] {
+                Tile::Air | Tile::Hero(_) => {
+                    reached[n] = true;
+                    q.push_back(n);
+                }
+                Tile::Mine(_) | Tile::Tavern => reached[n] = true,
+                Tile::Wall => (),
+            }
+        }
+    }
+
+    reached
+}
@@ -0,0 +1,39 @@
+use std::fs;
+use std::fs::File;
+use std::io::Read;
+
+use rand;
+use rand::Rng;
+
+use board::Board;
+
+pub fn load(path: &str) -> Result<Board, String> {
+    let mut text = String::new();
+    File::open(path)
+        .and_then(|mut f| f.read_to_string(&mut text))
+        .map_err(|e| format!("Could not read map {}: {}", path, e))?;
+
+    Board::parse_map(&text).map_err(|e| format!("{}: {}", path, e))
+}
+
+/// Returns the paths of all .map files in `dir`.
+pub fn list(dir: &str) -> Vec<String> {
+    match fs::read_dir(dir) {
+        Ok(entries) => {
+            entries.filter_map(|e| e.ok())
+                .map(|e| e.path().to_string_lossy().into_owned())
+                .filter(|p| p.ends_with(".map"))
+                .collect()
+        }
+        Err(_) => Vec::new(),
+    }
+}
+
+pub fn load_random(dir: &str) -> Result<Board, String> {
+    let maps = list(dir);
+    if maps.is_empty() {
+        return Err(format!("No maps found in {}", dir));
+    }
+
+    load(&maps[rand::thread_rng().gen_range(0, maps.len())])
+}
@@ -0,0 +1,235 @@
+use rand;
+use rand::Rng;
+use time;
+
+use direction::Direction;
+use policy;
+use policy::Policy;
+use state::State;
+
+/// Exploration constant of UCT, for rewards between 0 and 1.
+const EXPLORATION: f64 = 0.7;
+/// Hero moves played out after leaving the tree (ten rounds).
+const ROLLOUT_TURNS: usize = 40;
+/// Chance of a random move instead of the greedy one during playouts.
+const RANDOM_MOVE_P: f64 = 0.1;
+
+struct Node {
+    untried: Vec<Direction>,
+    children: Vec<(Direction, usize)>,
+    visits: f64,
+    reward: [f64; 4],
+}
+
+impl Node {
+    fn new(s: &State) -> Node {
+        let mut moves = if s.game.finished { Vec::new() } else { s.get_moves() };
+        rand::thread_rng().shuffle(&mut moves);
+
+        Node {
+            untried: moves,
+            children: Vec::new(),
+            visits: 0.0,
+            reward: [0.0; 4],
+        }
+    }
+}
+
+/// Monte Carlo Tree Search with UCT. Every hero picks its moves in the tree
+/// by its own reward, like in Max^n, and playouts use the greedy player with
+/// some random moves mixed in.
+pub struct Mcts {
+    move_time: i64,
+    tree: Vec<Node>,
+}
+
+impl Mcts {
+    pub fn new(move_time: i64) -> Mcts {
+        Mcts {
+            move_time: move_time,
+            tree: Vec::new(),
+        }
+    }
+
+    /// Runs one iteration from the root, leaving `s` as it was.
+    fn iterate(&mut self, s: &mut State) {
+        let mut path = vec![0];
+        let mut undo = Vec::new();
+        let mut node = 0;
+
+        // Selection and expansion.
+        while !s.game.finished {
+            if let Some(dir) = self.tree[node].untried.pop() {
+                undo.push(s.make_move(dir));
+
+                let child = self.tree.len();
+                self.tree.push(Node::new(s));
+                self.tree[node].children.push((dir, child));
+                path.push(child);
+                break;
+            }
+
+            if self.tree[node].children.is_empty() {
+                break;
+            }
+
+            let (dir, child) = self.select(node, s.game.turn % 4);
+            undo.push(s.make_move(dir));
+            path.push(child);
+            node = child;
+        }
+
+        // Playout.
+        let mut rng = rand::thread_rng();
+        for _ in 0..ROLLOUT_TURNS {
+            if s.game.finished {
+                break;
+            }
+
+            let dir = if rng.gen::<f64>() < RANDOM_MOVE_P {
+                let moves = s.get_moves();
+                moves[rng.gen_range(0, moves.len())]
+            } else {
+                policy::greedy_move(s)
+            };
+            undo.push(s.make_move(dir));
+        }
+
+        let reward = rewards(s);
+
+        for umi in undo.into_iter().rev() {
+            s.unmake_move(umi);
+        }
+
+        for n in path {
+            self.tree[n].visits += 1.0;
+            for (r, v) in self.tree[n].reward.iter_mut().zip(reward.iter()) {
+                *r += *v;
+            }
+        }
+    }
+
+    /// Picks the child with the best upper confidence bound for hero `idx`.
+    fn select(&self, node: usize, idx: usize) -> (Direction, usize) {
+        let log_n = self.tree[node].visits.ln();
+        let mut best = self.tree[node].children[0];
+        let mut best_ucb = f64::MIN;
+
+        for &(dir, child) in &self.tree[node].children {
+            let c = &self.tree[child];
+            let ucb = c.reward[idx] / c.visits + EXPLORATION * (log_n / c.visits).sqrt();
+
+            if ucb > best_ucb {
+                best_ucb = ucb;
+                best = (dir, child);
+            }
+        }
+
+        best
+    }
+}
+
+/// Each hero's share of the opponents it beats by predicted final gold,
+/// ties counting half.
+fn rewards(s: &State) -> [f64; 4] {
+    let turns_left = (s.game.max_turns - s.game.turn) / 4;
+    let mut predicted = [0usize; 4];
+    for (i, h) in s.game.heroes.iter().enumerate() {
+        predicted[i] = h.gold as usize + h.mine_count as usize * turns_left;
+    }
+
+    let mut result = [0f64; 4];
+    for i in 0..4 {
+        for j in 0..4 {
+            if predicted[i] > predicted[j] {
+                result[i] += 1.0 / 3.0;
+            } else if i != j && predicted[i] == predicted[j] {
+                result[i] += 0.5 / 3.0;
+            }
+        }
+    }
+
+    result
+}
+
+impl Policy for Mcts {
+    fn name(&self) -> String {
+        String::from("mcts")
+    }
+
+    fn choose_move(&mut self, s: &mut State) -> Direction {
+        let end_time = time::get_time() + time::Duration::milliseconds(self.move_time);
+
+        self.tree.clear();
+        self.tree.push(Node::new(s));
+
+        while time::get_time() < end_time {
+            self.iterate(s);
+        }
+
+        // The most visited move is the most robust choice.
+        let root = &self.tree[0];
+        let mut best = Direction::Stay;
+        let mut best_visits = -1.0;
+        for &(dir, child) in &root.children {
+            if self.tree[child].visits > best_visits {
+                best_visits = self.tree[child].visits;
+                best = dir;
+            }
+        }
+
+        best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mapgen;
+    use state::State;
+    use super::rewards;
+
+    /// A game of 100 turns per hero, `turn` turns in, where hero i has
+    /// `gold[i]` gold and `mines[i]` mines.
+    fn state(turn: usize, gold: [u16; 4], mines: [u8; 4]) -> State {
+        let names = [String::from("a"), String::from("b"), String::from("c"), String::from("d")];
+        let board = mapgen::generate(mapgen::size_for_seed(1), 1);
+        let mut s = State::new("test", board, 400, &names);
+
+        s.game.turn = turn;
+        s.game.finished = turn == s.game.max_turns;
+        for (i, h) in s.game.heroes.iter_mut().enumerate() {
+            h.gold = gold[i];
+            h.mine_count = mines[i];
+        }
+        s
+    }
+
+    fn assert_rewards(s: &State, expected: [f64; 4]) {
+        let r = rewards(s);
+        for i in 0..4 {
+            assert!((r[i] - expected[i]).abs() < 1e-9, "{:?} vs. {:?}", r, expected);
+        }
+    }
+
+    #[test]
+    fn mines_count_for_the_turns_left() {
+        // 50 turns per hero left: 100, 0 + 3 * 50, 60 + 50 and 0.
+        let s = state(200, [100, 0, 60, 0], [0, 3, 1, 0]);
+        assert_rewards(&s, [1.0 / 3.0, 1.0, 2.0 / 3.0, 0.0]);
+    }
+
+    #[test]
+    fn ties_count_half() {
+        let s = state(0, [10, 10, 10, 10], [0, 0, 0, 0]);
+        assert_rewards(&s, [0.5; 4]);
+
+        let s = state(0, [20, 20, 5, 0], [0, 0, 0, 0]);
+        assert_rewards(&s, [2.5 / 3.0, 2.5 / 3.0, 1.0 / 3.0, 0.0]);
+    }
+
+    #[test]
+    fn only_gold_counts_when_the_game_is_finished() {
+        let s = state(400, [30, 20, 10, 0], [0, 5, 5, 9]);
+        assert_rewards(&s, [1.0, 2.0 / 3.0, 1.0 / 3.0, 0.0]);
+    }
+}
@@ -17,3 +17,20 @@ impl Default for Move {
         }
     }
 }
+
+impl Move {
+    /// Describes the move as seen by hero `hero_id`, who plays `player` 0.
+    pub fn describe(&self, hero_id: usize) -> String {
+        if self.player == 0 {
+            return format!("@{} {}", hero_id, self.directions[0]);
+        }
+
+        if self.directions.iter().all(|d| *d == self.directions[0]) {
+            return format!("others {}", self.directions[0]);
+        }
+
+        format!("@{} {}",
+                (hero_id - 1 + self.player as usize) % 4 + 1,
+                self.directions[self.player as usize])
+    }
+}
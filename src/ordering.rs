@@ -0,0 +1,125 @@
+use direction::Direction;
+use mv::Move;
+use position::Position;
+
+/// Largest board side, as in the Zobrist table.
+const MAX_SIZE: usize = 35;
+/// Killer slots, indexed by game turn. A search never spans more turns.
+const KILLER_SLOTS: usize = 256;
+/// History scores are halved when one of them gets this large.
+const HISTORY_LIMIT: u32 = 1 << 20;
+
+/// Which move ordering heuristics to use, besides the move from the
+/// transposition table and the threat list.
+#[derive(Clone, Copy, Debug)]
+pub struct MoveOrdering {
+    pub killers: bool,
+    pub counter_moves: bool,
+    pub history: bool,
+}
+
+impl Default for MoveOrdering {
+    fn default() -> MoveOrdering {
+        MoveOrdering {
+            killers: true,
+            counter_moves: true,
+            history: true,
+        }
+    }
+}
+
+fn square(hero_id: usize, pos: &Position) -> usize {
+    (hero_id - 1) * MAX_SIZE * MAX_SIZE + pos.x as usize * MAX_SIZE + pos.y as usize
+}
+
+/// How often moving a hero from a square in a direction was the best move,
+/// weighted by the depth it was found at.
+pub struct History {
+    scores: Vec<u32>,
+}
+
+impl History {
+    pub fn new() -> History {
+        History { scores: vec![0; 4 * MAX_SIZE * MAX_SIZE * 5] }
+    }
+
+    pub fn score(&self, hero_id: usize, pos: &Position, dir: Direction) -> u32 {
+        self.scores[square(hero_id, pos) * 5 + dir as usize]
+    }
+
+    pub fn reward(&mut self, hero_id: usize, pos: &Position, dir: Direction, depth: u8) {
+        let idx = square(hero_id, pos) * 5 + dir as usize;
+        self.scores[idx] += depth as u32 * depth as u32;
+
+        if self.scores[idx] >= HISTORY_LIMIT {
+            self.age();
+        }
+    }
+
+    /// Halves all scores, so older searches count less.
+    pub fn age(&mut self) {
+        for s in self.scores.iter_mut() {
+            *s /= 2;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        for s in self.scores.iter_mut() {
+            *s = 0;
+        }
+    }
+}
+
+/// The last two moves that caused a cutoff on each ply.
+pub struct Killers {
+    moves: Vec<[Option<Move>; 2]>,
+}
+
+impl Killers {
+    pub fn new() -> Killers {
+        Killers { moves: vec![[None; 2]; KILLER_SLOTS] }
+    }
+
+    pub fn get(&self, turn: usize) -> [Option<Move>; 2] {
+        self.moves[turn % KILLER_SLOTS]
+    }
+
+    pub fn insert(&mut self, turn: usize, mv: Move) {
+        let slot = &mut self.moves[turn % KILLER_SLOTS];
+        if slot[0] != Some(mv) {
+            slot[1] = slot[0];
+            slot[0] = Some(mv);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        for slot in self.moves.iter_mut() {
+            *slot = [None; 2];
+        }
+    }
+}
+
+/// The move that last refuted a hero arriving on a square.
+pub struct CounterMoves {
+    moves: Vec<Option<Move>>,
+}
+
+impl CounterMoves {
+    pub fn new() -> CounterMoves {
+        CounterMoves { moves: vec![None; 4 * MAX_SIZE * MAX_SIZE] }
+    }
+
+    pub fn get(&self, hero_id: usize, pos: &Position) -> Option<Move> {
+        self.moves[square(hero_id, pos)]
+    }
+
+    pub fn set(&mut self, hero_id: usize, pos: &Position, mv: Move) {
+        self.moves[square(hero_id, pos)] = Some(mv);
+    }
+
+    pub fn clear(&mut self) {
+        for mv in self.moves.iter_mut() {
+            *mv = None;
+        }
+    }
+}
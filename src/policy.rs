@@ -0,0 +1,165 @@
+use rand;
+use rand::Rng;
+
+use std::sync::Arc;
+
+use bot::Bot;
+use direction::Direction;
+use evaluator;
+use evaluator::Model;
+use mcts::Mcts;
+use search;
+use state::State;
+use weights::Weights;
+
+/// Anything that can control a hero. The hero to move is `s.hero`.
+pub trait Policy {
+    fn name(&self) -> String;
+
+    fn new_game(&mut self) {}
+
+    fn choose_move(&mut self, s: &mut State) -> Direction;
+}
+
+impl Policy for Bot {
+    fn name(&self) -> String {
+        match self.strategy_name() {
+            "mtdf" => String::from("beagle"),
+            name => format!("beagle:{}", name),
+        }
+    }
+
+    fn new_game(&mut self) {
+        Bot::new_game(self)
+    }
+
+    fn choose_move(&mut self, s: &mut State) -> Direction {
+        Bot::choose_move(self, s)
+    }
+}
+
+/// Picks uniformly among the legal moves, like the training bots.
+pub struct Random;
+
+impl Policy for Random {
+    fn name(&self) -> String {
+        String::from("random")
+    }
+
+    fn choose_move(&mut self, s: &mut State) -> Direction {
+        let moves = s.get_moves();
+        if moves.is_empty() {
+            return Direction::Stay;
+        }
+
+        moves[rand::thread_rng().gen_range(0, moves.len())]
+    }
+}
+
+/// Walks to the closest mine it doesn't own, and to the closest tavern when
+/// it couldn't survive taking the mine.
+pub struct Greedy;
+
+impl Policy for Greedy {
+    fn name(&self) -> String {
+        String::from("greedy")
+    }
+
+    fn choose_move(&mut self, s: &mut State) -> Direction {
+        greedy_move(s)
+    }
+}
+
+/// The move of the greedy player for the hero whose turn it is.
+pub fn greedy_move(s: &mut State) -> Direction {
+    let h = s.game.heroes[s.game.turn % 4].clone();
+    let (mdist, mpos) = s.game.board.get_closest_mine(&h.pos, h.id);
+
+    let target = match mpos {
+        Some(m) if h.life as u16 > mdist as u16 + 20 || h.gold < 2 => m,
+        _ if h.gold >= 2 && h.life < 90 => {
+            let (_, tpos) = s.game.board.get_closest_tavern(&h.pos);
+            tpos
+        }
+        _ => return Direction::Stay,
+    };
+
+    s.game.board.direction_to(&h.pos, &target)
+}
+
+/// A player that goes by a name of its own, such as Beagle with weights
+/// from a file, so it can be told apart in the standings.
+struct Named {
+    name: String,
+    inner: Box<dyn Policy>,
+}
+
+impl Policy for Named {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn new_game(&mut self) {
+        self.inner.new_game()
+    }
+
+    fn choose_move(&mut self, s: &mut State) -> Direction {
+        self.inner.choose_move(s)
+    }
+}
+
+/// Creates a player by name. `beagle:NAME` is Beagle with the search
+/// algorithm NAME, `beagle+null-moves` Beagle with null move pruning, and
+/// `beagle@FILE` Beagle with the evaluation weights or the model in FILE.
+/// Players with options keep their full name, so they play as a team of
+/// their own.
+pub fn from_name(name: &str, move_time: i64) -> Result<Box<dyn Policy>, String> {
+    let mut at = name.splitn(2, '@');
+    let player = at.next().unwrap_or("");
+    let weights = at.next();
+    let mut plus = player.split('+');
+    let mut parts = plus.next().unwrap_or("").splitn(2, ':');
+    let options: Vec<&str> = plus.collect();
+    let plain = weights.is_none() && options.is_empty();
+
+    match (parts.next(), parts.next()) {
+        (Some("beagle"), search) => {
+            let mut bot = Bot::with_table_size(1000000u64);
+            bot.set_move_time(move_time);
+            bot.set_verbose(false);
+            if let Some(search) = search {
+                let strategy = search::from_name(search)
+                    .ok_or_else(|| format!("Unknown search algorithm: {}", search))?;
+                bot.set_strategy(strategy);
+            }
+
+            for option in &options {
+                match *option {
+                    "null-moves" => bot.set_null_moves(true),
+                    _ => return Err(format!("Unknown option for beagle: {}", option)),
+                }
+            }
+
+            if let Some(path) = weights {
+                if evaluator::is_model_file(path)? {
+                    bot.set_evaluator(Arc::new(Model::load(path)?));
+                } else {
+                    bot.set_weights(Weights::load(path)?);
+                }
+            }
+
+            if plain {
+                Ok(Box::new(bot))
+            } else {
+                Ok(Box::new(Named {
+                    name: String::from(name),
+                    inner: Box::new(bot),
+                }))
+            }
+        }
+        (Some("random"), None) if plain => Ok(Box::new(Random)),
+        (Some("greedy"), None) if plain => Ok(Box::new(Greedy)),
+        (Some("mcts"), None) if plain => Ok(Box::new(Mcts::new(move_time))),
+        _ => Err(format!("Unknown player: {}", name)),
+    }
+}
@@ -1,6 +1,6 @@
 use direction::Direction;
 
-#[derive(Clone, Copy, Deserialize, Debug, Eq, PartialEq, Hash)]
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Eq, PartialEq, Hash)]
 pub struct Position {
     pub x: i8,
     pub y: i8,
@@ -0,0 +1,83 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use serde_json;
+
+use bot::SearchInfo;
+use direction::Direction;
+use state::State;
+
+/// One line of a replay file: a state as the server sent it, followed by the
+/// move Beagle answered with and what the search behind it found. The last
+/// state of a game has neither.
+#[derive(Serialize, Deserialize)]
+pub struct Turn {
+    pub state: State,
+    #[serde(rename="move")]
+    pub mv: Option<Direction>,
+    pub search: Option<SearchInfo>,
+}
+
+pub struct ReplayWriter {
+    out: BufWriter<File>,
+    last_turn: Option<usize>,
+}
+
+impl ReplayWriter {
+    /// Creates `dir/<game id>.jsonl`.
+    pub fn create(dir: &str, game_id: &str) -> Result<ReplayWriter, String> {
+        let path = Path::new(dir).join(format!("{}.jsonl", game_id));
+        File::create(&path)
+            .map(|f| {
+                ReplayWriter {
+                    out: BufWriter::new(f),
+                    last_turn: None,
+                }
+            })
+            .map_err(|e| format!("Could not create {}: {}", path.display(), e))
+    }
+
+    pub fn record(&mut self, state: &State, mv: Option<Direction>, search: Option<&SearchInfo>) {
+        self.last_turn = Some(state.game.turn);
+        let turn = Turn {
+            state: state.clone(),
+            mv: mv,
+            search: search.cloned(),
+        };
+
+        let res = writeln!(self.out, "{}", serde_json::to_string(&turn).unwrap())
+            .and_then(|_| self.out.flush());
+        if let Err(e) = res {
+            println!("Could not write replay: {}", e);
+        }
+    }
+
+    /// Ends the replay with the last state the server sent, unless that
+    /// state was already recorded along with our move to it.
+    pub fn finish(&mut self, state: &State) {
+        if self.last_turn != Some(state.game.turn) {
+            self.record(state, None, None);
+        }
+    }
+}
+
+/// Reads a replay file, with the boards of all states initialized.
+pub fn load(path: &str) -> Result<Vec<Turn>, String> {
+    let f = File::open(path).map_err(|e| format!("Could not open {}: {}", path, e))?;
+    let mut result = Vec::new();
+
+    for (i, line) in BufReader::new(f).lines().enumerate() {
+        let line = line.map_err(|e| format!("{}: {}", path, e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let mut turn: Turn = serde_json::from_str(&line)
+            .map_err(|e| format!("{}:{}: {}", path, i + 1, e))?;
+        turn.state.game.board.initialize();
+        result.push(turn);
+    }
+
+    Ok(result)
+}
@@ -0,0 +1,159 @@
+use std::process;
+
+use rand;
+use rand::Rng;
+
+use board::Board;
+use config::MatchConfig;
+use direction::Direction;
+use mapgen;
+use maps;
+use policy;
+use policy::Policy;
+use state::State;
+
+#[derive(Clone, Default)]
+pub struct Standing {
+    pub games: usize,
+    pub wins: usize,
+    pub gold: u64,
+    pub rank_sum: usize,
+}
+
+/// Plays one game to the end. `players[i]` controls hero `i + 1`.
+pub fn play_game(mut state: State, players: &mut [Box<dyn Policy>]) -> State {
+    for p in players.iter_mut() {
+        p.new_game();
+    }
+
+    while !state.game.finished {
+        let idx = state.game.turn % 4;
+
+        let dir = if state.game.heroes[idx].crashed {
+            Direction::Stay
+        } else {
+            state.hero = state.game.heroes[idx].clone();
+            players[idx].choose_move(&mut state)
+        };
+
+        state.make_move(dir);
+    }
+
+    state
+}
+
+/// Returns the rank of every hero, with equal gold sharing a rank.
+pub fn ranks(s: &State) -> [usize; 4] {
+    let mut result = [1; 4];
+
+    for (i, h) in s.game.heroes.iter().enumerate() {
+        result[i] += s.game.heroes.iter().filter(|e| e.gold > h.gold).count();
+    }
+
+    result
+}
+
+fn next_board(config: &MatchConfig, game: usize) -> Result<Board, String> {
+    if let Some(ref path) = config.map {
+        return maps::load(path);
+    }
+
+    if let Some(ref dir) = config.maps {
+        return maps::load_random(dir);
+    }
+
+    let seed = match config.seed {
+        Some(s) => s.wrapping_add(game as u64),
+        None => rand::thread_rng().gen(),
+    };
+    let size = config.size.unwrap_or_else(|| mapgen::size_for_seed(seed));
+
+    Ok(mapgen::generate(size, seed))
+}
+
+pub fn run(config: &MatchConfig) {
+    let mut players: Vec<Box<dyn Policy>> = Vec::with_capacity(4);
+    for name in &config.players {
+        match policy::from_name(name, config.move_time) {
+            Ok(p) => players.push(p),
+            Err(e) => {
+                println!("{}", e);
+                process::exit(1);
+            }
+        }
+    }
+
+    let mut standings = vec![Standing::default(); 4];
+
+    for game in 0..config.games {
+        let board = match next_board(config, game) {
+            Ok(b) => b,
+            Err(e) => {
+                println!("{}", e);
+                process::exit(1);
+            }
+        };
+
+        // Rotate the players through the seats, so nobody keeps the same
+        // spawn point for the whole match.
+        players.rotate_right(1);
+        standings.rotate_right(1);
+
+        let names = [players[0].name(),
+                     players[1].name(),
+                     players[2].name(),
+                     players[3].name()];
+
+        let state = State::new(&format!("match-{}", game + 1), board, 4 * config.turns, &names);
+        let state = play_game(state, &mut players);
+        let ranks = ranks(&state);
+
+        let mut line = format!("Game {}:", game + 1);
+        for (i, h) in state.game.heroes.iter().enumerate() {
+            standings[i].games += 1;
+            standings[i].gold += h.gold as u64;
+            standings[i].rank_sum += ranks[i];
+            if ranks[i] == 1 {
+                standings[i].wins += 1;
+            }
+
+            line.push_str(&format!(" {} {} ({}),", h.name, h.gold, ranks[i]));
+        }
+        println!("{}", line.trim_end_matches(','));
+    }
+
+    // Undo the rotation, so the results are listed in command line order.
+    let shift = config.games % 4;
+    players.rotate_left(shift);
+    standings.rotate_left(shift);
+
+    println!("\nplayer            games  wins  avg gold  avg rank");
+    for (p, st) in players.iter().zip(standings.iter()) {
+        if st.games == 0 {
+            continue;
+        }
+
+        println!("{:<16} {:>6} {:>5} {:>9.1} {:>9.2}",
+                 p.name(),
+                 st.games,
+                 st.wins,
+                 st.gold as f64 / st.games as f64,
+                 st.rank_sum as f64 / st.games as f64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use config::MatchConfig;
+    use mapgen;
+    use super::next_board;
+
+    #[test]
+    fn seeds_wrap_around() {
+        let config = MatchConfig { seed: Some(u64::MAX), ..MatchConfig::default() };
+
+        let board = next_board(&config, 1).unwrap();
+        let expected = mapgen::generate(mapgen::size_for_seed(0), 0);
+        assert_eq!(format!("{}", board), format!("{}", expected));
+    }
+}
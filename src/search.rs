@@ -0,0 +1,438 @@
+use std::sync::Arc;
+
+use time;
+
+use bot;
+use bot::Bot;
+use direction::Direction;
+use mv::Move;
+use state::State;
+
+/// A multi-player search algorithm. All of them share the evaluation, move
+/// generation and transposition table of the `Bot` they run on, so they can
+/// be compared on equal terms.
+pub trait SearchStrategy: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Searches `s` to `depth` and returns its score for `s.hero`, or None if
+    /// the search was stopped. `guess` is the score of the previous
+    /// iteration.
+    fn search(&self,
+              bot: &mut Bot,
+              s: &mut State,
+              guess: i32,
+              depth: u8,
+              nodes: &mut u64,
+              end_time: time::Timespec)
+              -> Option<i32>;
+
+    /// Whether Best Reply Search should search every move after the first
+    /// with a null window, and only search it again with the full window if
+    /// it turns out better (Principal Variation Search).
+    fn principal_variation(&self) -> bool {
+        false
+    }
+
+    /// Plays a move the way this strategy stores it in the transposition
+    /// table. Best Reply Search stores a move for all three opponents at
+    /// once.
+    fn play(&self, s: &mut State, mv: &Move) {
+        if s.game.turn % 4 == s.hero.id - 1 {
+            s.make_move(mv.directions[0]);
+        } else {
+            s.make_move(mv.directions[1]);
+            s.make_move(mv.directions[2]);
+            s.make_move(mv.directions[3]);
+        }
+    }
+}
+
+pub const NAMES: [&str; 5] = ["mtdf", "pvs", "brs", "paranoid", "maxn"];
+
+/// Half the width of the first aspiration window of Pvs.
+const ASPIRATION_WINDOW: i32 = 50;
+
+pub fn from_name(name: &str) -> Option<Arc<dyn SearchStrategy>> {
+    match name {
+        "mtdf" => Some(Arc::new(Mtdf)),
+        "pvs" => Some(Arc::new(Pvs)),
+        "brs" => Some(Arc::new(Brs)),
+        "paranoid" => Some(Arc::new(Paranoid)),
+        "maxn" => Some(Arc::new(MaxN)),
+        _ => None,
+    }
+}
+
+/// Best Reply Search with a full window: after each of our moves, only the
+/// opponent whose best move hurts us most gets to move.
+pub struct Brs;
+
+impl SearchStrategy for Brs {
+    fn name(&self) -> &'static str {
+        "brs"
+    }
+
+    fn search(&self,
+              bot: &mut Bot,
+              s: &mut State,
+              _guess: i32,
+              depth: u8,
+              nodes: &mut u64,
+              end_time: time::Timespec)
+              -> Option<i32> {
+        bot.stats.root_searches += 1;
+        bot.brs(s, i32::MIN, i32::MAX, depth, end_time, nodes)
+    }
+}
+
+/// Best Reply Search driven by MTD(f), which closes in on the score with
+/// null window searches starting from the previous iteration's score.
+pub struct Mtdf;
+
+impl SearchStrategy for Mtdf {
+    fn name(&self) -> &'static str {
+        "mtdf"
+    }
+
+    fn search(&self,
+              bot: &mut Bot,
+              s: &mut State,
+              guess: i32,
+              depth: u8,
+              nodes: &mut u64,
+              end_time: time::Timespec)
+              -> Option<i32> {
+        let mut f = guess;
+        let mut upper = i32::MAX;
+        let mut lower = i32::MIN;
+        let step_size = 25i32;
+
+        while upper == i32::MAX || lower == i32::MIN {
+            bot.stats.root_searches += 1;
+            let g = bot.brs(s, f - 1, f, depth, end_time, nodes)?;
+
+            if g < f { upper = g } else { lower = g }
+
+            if upper == g {
+                f = g - step_size;
+            } else {
+                f = g + step_size;
+            }
+        }
+
+        if lower == upper {
+            return Some(lower);
+        }
+
+        bot.stats.root_searches += 1;
+        bot.brs(s, lower, upper, depth, end_time, nodes)
+    }
+}
+
+/// Best Reply Search with Principal Variation Search inside, started with an
+/// aspiration window around the previous iteration's score. A search that
+/// falls outside the window is repeated with a window four times as wide
+/// around its result, so big swings of the evaluation cost a few searches
+/// instead of the many small steps of MTD(f).
+pub struct Pvs;
+
+impl SearchStrategy for Pvs {
+    fn name(&self) -> &'static str {
+        "pvs"
+    }
+
+    fn principal_variation(&self) -> bool {
+        true
+    }
+
+    fn search(&self,
+              bot: &mut Bot,
+              s: &mut State,
+              guess: i32,
+              depth: u8,
+              nodes: &mut u64,
+              end_time: time::Timespec)
+              -> Option<i32> {
+        let mut center = guess;
+        let mut delta = ASPIRATION_WINDOW;
+
+        loop {
+            let alpha = center.saturating_sub(delta);
+            let beta = center.saturating_add(delta);
+
+            bot.stats.root_searches += 1;
+            let g = bot.brs(s, alpha, beta, depth, end_time, nodes)?;
+
+            if (g > alpha || alpha == i32::MIN) && (g < beta || beta == i32::MAX) {
+                return Some(g);
+            }
+
+            center = g;
+            delta = delta.saturating_mul(4);
+        }
+    }
+}
+
+/// The hero a move at this node belongs to, counted from `s.hero`.
+fn player(s: &State) -> usize {
+    (s.game.turn + 4 - (s.hero.id - 1)) % 4
+}
+
+fn single_move(player: usize, dir: Direction) -> Move {
+    let mut directions = [Direction::Stay; 4];
+    directions[player] = dir;
+
+    Move {
+        player: player as u8,
+        directions: directions,
+    }
+}
+
+/// Puts the move stored for `s` in the table, if any, in front.
+fn order_moves(bot: &Bot, s: &State, hash: u64, moves: &mut [Direction]) {
+    let player = player(s);
+
+    if let Some(e) = bot.table().probe(hash) {
+        if e.mv.player as usize == player {
+            if let Some(i) = moves.iter().position(|d| *d == e.mv.directions[player]) {
+                moves.swap(0, i);
+            }
+        }
+    }
+}
+
+/// The scores (`alpha`, `beta`) a search is interested in. Scores outside
+/// of it are only bounds.
+#[derive(Clone, Copy)]
+pub struct Window {
+    pub alpha: i32,
+    pub beta: i32,
+}
+
+impl Window {
+    pub fn full() -> Window {
+        Window {
+            alpha: i32::MIN,
+            beta: i32::MAX,
+        }
+    }
+}
+
+/// Paranoid search: every hero moves in turn and all opponents play against
+/// us, which makes the game two-player and alpha-beta applicable. Depth is
+/// counted in single hero moves.
+pub struct Paranoid;
+
+impl Paranoid {
+    fn alpha_beta(&self,
+                  bot: &mut Bot,
+                  s: &mut State,
+                  window: Window,
+                  depth: u8,
+                  nodes: &mut u64,
+                  end_time: time::Timespec)
+                  -> Option<i32> {
+        let Window { mut alpha, mut beta } = window;
+        let hash = bot::position_hash(s);
+
+        if let Some(e) = bot.table().probe(hash) {
+            if e.depth >= s.game.turn as u16 + depth as u16 {
+                if e.lower >= beta {
+                    return Some(e.lower);
+                }
+                if e.upper <= alpha {
+                    return Some(e.upper);
+                }
+
+                alpha = alpha.max(e.lower);
+                beta = beta.min(e.upper);
+            }
+        }
+
+        *nodes += 1;
+        if bot.out_of_time(*nodes, end_time) {
+            return None;
+        }
+
+        if depth == 0 || s.game.finished {
+            return Some(bot.eval(s));
+        }
+
+        let player = player(s);
+        let mut moves = s.get_moves();
+        order_moves(bot, s, hash, &mut moves);
+
+        let mut best = Direction::Stay;
+        let mut g;
+
+        if player == 0 {
+            g = i32::MIN;
+            let mut a = alpha;
+
+            for dir in moves {
+                let umi = s.make_move(dir);
+                let window = Window {
+                    alpha: a,
+                    beta: beta,
+                };
+                let v = self.alpha_beta(bot, s, window, depth - 1, nodes, end_time);
+                s.unmake_move(umi);
+
+                let v = v?;
+                if v > g {
+                    g = v;
+                    best = dir;
+                }
+                a = a.max(g);
+                if g >= beta {
+                    break;
+                }
+            }
+        } else {
+            g = i32::MAX;
+            let mut b = beta;
+
+            for dir in moves {
+                let umi = s.make_move(dir);
+                let window = Window {
+                    alpha: alpha,
+                    beta: b,
+                };
+                let v = self.alpha_beta(bot, s, window, depth - 1, nodes, end_time);
+                s.unmake_move(umi);
+
+                let v = v?;
+                if v < g {
+                    g = v;
+                    best = dir;
+                }
+                b = b.min(g);
+                if g <= alpha {
+                    break;
+                }
+            }
+        }
+
+        let window = Window {
+            alpha: alpha,
+            beta: beta,
+        };
+        bot.store(s, hash, depth, g, window, single_move(player, best));
+        Some(g)
+    }
+}
+
+impl SearchStrategy for Paranoid {
+    fn name(&self) -> &'static str {
+        "paranoid"
+    }
+
+    fn search(&self,
+              bot: &mut Bot,
+              s: &mut State,
+              _guess: i32,
+              depth: u8,
+              nodes: &mut u64,
+              end_time: time::Timespec)
+              -> Option<i32> {
+        bot.stats.root_searches += 1;
+        self.alpha_beta(bot, s, Window::full(), depth, nodes, end_time)
+    }
+
+    fn play(&self, s: &mut State, mv: &Move) {
+        s.make_move(mv.directions[mv.player as usize]);
+    }
+}
+
+/// Max^n: every hero moves in turn and picks the move that is best for
+/// itself, judged by the evaluation from its point of view. There is no
+/// pruning, so it searches the least deep. Depth is counted in single hero
+/// moves.
+pub struct MaxN;
+
+impl MaxN {
+    fn max_n(&self,
+             bot: &mut Bot,
+             s: &mut State,
+             depth: u8,
+             nodes: &mut u64,
+             end_time: time::Timespec)
+             -> Option<[i32; 4]> {
+        *nodes += 1;
+        if bot.out_of_time(*nodes, end_time) {
+            return None;
+        }
+
+        if depth == 0 || s.game.finished {
+            return Some(eval_all(bot, s));
+        }
+
+        let hash = bot::position_hash(s);
+        let idx = s.game.turn % 4;
+        let mut moves = s.get_moves();
+        order_moves(bot, s, hash, &mut moves);
+
+        let mut best: Option<[i32; 4]> = None;
+        let mut best_dir = Direction::Stay;
+
+        for dir in moves {
+            let umi = s.make_move(dir);
+            let v = self.max_n(bot, s, depth - 1, nodes, end_time);
+            s.unmake_move(umi);
+
+            let v = v?;
+            if best.is_none_or(|b| v[idx] > b[idx]) {
+                best = Some(v);
+                best_dir = dir;
+            }
+        }
+
+        // The table only keeps the move here, for ordering and the PV.
+        let best = best.unwrap();
+        let score = best[s.hero.id - 1];
+        let exact = Window {
+            alpha: score - 1,
+            beta: score + 1,
+        };
+        bot.store(s, hash, depth, score, exact, single_move(player(s), best_dir));
+
+        Some(best)
+    }
+}
+
+/// The evaluation from every hero's point of view.
+fn eval_all(bot: &mut Bot, s: &mut State) -> [i32; 4] {
+    let hero = s.hero.clone();
+    let mut result = [0; 4];
+
+    for (i, r) in result.iter_mut().enumerate() {
+        s.hero = s.game.heroes[i].clone();
+        *r = bot.eval(s);
+    }
+
+    s.hero = hero;
+    result
+}
+
+impl SearchStrategy for MaxN {
+    fn name(&self) -> &'static str {
+        "maxn"
+    }
+
+    fn search(&self,
+              bot: &mut Bot,
+              s: &mut State,
+              _guess: i32,
+              depth: u8,
+              nodes: &mut u64,
+              end_time: time::Timespec)
+              -> Option<i32> {
+        let hero = s.hero.id - 1;
+        bot.stats.root_searches += 1;
+        self.max_n(bot, s, depth, nodes, end_time).map(|v| v[hero])
+    }
+
+    fn play(&self, s: &mut State, mv: &Move) {
+        s.make_move(mv.directions[mv.player as usize]);
+    }
+}
@@ -0,0 +1,453 @@
+use std::collections::HashMap;
+use std::io::Read;
+use std::process;
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use hyper::header::ContentType;
+use hyper::method::Method;
+use hyper::server::{Handler, Request, Response, Server};
+use hyper::status::StatusCode;
+use hyper::uri::RequestUri;
+use rand;
+use rand::Rng;
+use serde_json;
+
+use board::Board;
+use config::ServerConfig;
+use direction::Direction;
+use mapgen;
+use maps;
+use policy::{Policy, Random};
+use state::State;
+
+const DEFAULT_TURNS: usize = 300;
+
+type Reply = Result<String, (StatusCode, String)>;
+
+fn bad_request(msg: &str) -> (StatusCode, String) {
+    (StatusCode::BadRequest, format!("Vindinium - {}", msg))
+}
+
+fn not_found(msg: &str) -> (StatusCode, String) {
+    (StatusCode::NotFound, format!("Vindinium - {}", msg))
+}
+
+enum Seat {
+    Remote { key: String, token: String },
+    Random,
+}
+
+struct Match {
+    state: State,
+    seats: Vec<Seat>,
+    deadline: Option<Instant>,
+    finished_at: Option<Instant>,
+}
+
+#[derive(Default)]
+struct Lobby {
+    games: HashMap<String, Match>,
+    queue: Vec<(u64, String)>,
+    seated: HashMap<u64, (String, usize)>,
+    next_ticket: u64,
+}
+
+impl Lobby {
+    /// Drops games that ended long enough ago that nobody is waiting on them.
+    fn purge(&mut self) {
+        self.games.retain(|_, m| match m.finished_at {
+            Some(t) => t.elapsed() < Duration::from_secs(60),
+            None => true,
+        });
+    }
+}
+
+/// A Vindinium-compatible server for offline play. Training games put the
+/// player against three random movers; arena games start as soon as four
+/// players are queued. Games are played on generated maps unless a training
+/// game asks for one of the maps in the maps directory.
+pub struct LocalServer {
+    config: ServerConfig,
+    lobby: Mutex<Lobby>,
+    changed: Condvar,
+}
+
+pub fn serve(config: ServerConfig) {
+    let address = config.address.clone();
+    let handler = LocalServer {
+        config: config,
+        lobby: Mutex::new(Lobby::default()),
+        changed: Condvar::new(),
+    };
+
+    let server = match Server::http(&address[..]) {
+        Ok(s) => s,
+        Err(e) => {
+            println!("Could not listen on {}: {}", address, e);
+            process::exit(1);
+        }
+    };
+
+    println!("Listening on http://{}", address);
+
+    // Dropping the guard blocks until the server shuts down.
+    let _listening = server.handle_threads(handler, 32).unwrap();
+}
+
+fn new_token() -> String {
+    rand::thread_rng().gen_ascii_chars().take(8).collect()
+}
+
+fn decode(s: &str) -> String {
+    let b = s.as_bytes();
+    let mut result = Vec::with_capacity(b.len());
+    let mut i = 0;
+
+    while i < b.len() {
+        match b[i] {
+            b'+' => result.push(b' '),
+            b'%' if i + 2 < b.len() => {
+                let hex = String::from_utf8_lossy(&b[i + 1..i + 3]);
+                match u8::from_str_radix(&hex, 16) {
+                    Ok(c) => {
+                        result.push(c);
+                        i += 2;
+                    }
+                    Err(_) => result.push(b'%'),
+                }
+            }
+            c => result.push(c),
+        }
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&result).into_owned()
+}
+
+fn parse_form(body: &str) -> HashMap<String, String> {
+    let mut result = HashMap::new();
+
+    for pair in body.split('&') {
+        let mut kv = pair.splitn(2, '=');
+        if let (Some(k), Some(v)) = (kv.next(), kv.next()) {
+            result.insert(decode(k), decode(v));
+        }
+    }
+
+    result
+}
+
+fn parse_dir(dir: Option<&String>) -> Result<Direction, (StatusCode, String)> {
+    match dir.map(|d| d.to_lowercase()) {
+        Some(ref d) if d == "north" => Ok(Direction::North),
+        Some(ref d) if d == "east" => Ok(Direction::East),
+        Some(ref d) if d == "south" => Ok(Direction::South),
+        Some(ref d) if d == "west" => Ok(Direction::West),
+        Some(ref d) if d == "stay" => Ok(Direction::Stay),
+        _ => Err(bad_request("Invalid direction")),
+    }
+}
+
+impl Handler for LocalServer {
+    fn handle(&self, mut req: Request, mut res: Response) {
+        let path = match req.uri {
+            RequestUri::AbsolutePath(ref p) => String::from(p.split('?').next().unwrap()),
+            _ => String::default(),
+        };
+
+        let mut body = String::new();
+        let reply = if req.method != Method::Post {
+            Err((StatusCode::MethodNotAllowed, String::from("Vindinium - POST only")))
+        } else if req.read_to_string(&mut body).is_err() {
+            Err(bad_request("Could not read request"))
+        } else {
+            self.route(&path, &parse_form(&body))
+        };
+
+        match reply {
+            Ok(json) => {
+                res.headers_mut().set(ContentType::json());
+                res.send(json.as_bytes()).ok();
+            }
+            Err((status, msg)) => {
+                *res.status_mut() = status;
+                res.send(msg.as_bytes()).ok();
+            }
+        }
+    }
+}
+
+impl LocalServer {
+    fn route(&self, path: &str, params: &HashMap<String, String>) -> Reply {
+        let key = match params.get("key") {
+            Some(k) if !k.is_empty() => k.clone(),
+            _ => return Err(bad_request("Key is missing")),
+        };
+
+        let parts: Vec<&str> = path.trim_matches('/').split('/').collect();
+
+        match parts.as_slice() {
+            &["api", "training"] => self.training(key, params),
+            &["api", "arena"] => self.arena(key),
+            &["api", id, token, "play"] => self.play(id, token, &key, params.get("dir")),
+            _ => Err(not_found("Not found")),
+        }
+    }
+
+    fn load_map(&self, name: Option<&String>) -> Result<Board, (StatusCode, String)> {
+        match name {
+            Some(n) => {
+                if n.is_empty() ||
+                   !n.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_') {
+                    return Err(bad_request("Invalid map name"));
+                }
+
+                maps::load(&format!("{}/{}.map", self.config.maps, n))
+            }
+            None => {
+                let seed = rand::thread_rng().gen();
+                Ok(mapgen::generate(mapgen::size_for_seed(seed), seed))
+            }
+        }
+        .map_err(|e| bad_request(&e))
+    }
+
+    fn training(&self, key: String, params: &HashMap<String, String>) -> Reply {
+        let turns = match params.get("turns") {
+            Some(t) => {
+                match t.parse::<usize>() {
+                    Ok(t) if t > 0 => t,
+                    _ => return Err(bad_request("Invalid number of turns")),
+                }
+            }
+            None => DEFAULT_TURNS,
+        };
+
+        let board = self.load_map(params.get("map"))?;
+        let player = rand::thread_rng().gen_range(0, 4);
+
+        let mut names = [String::from("random"),
+                         String::from("random"),
+                         String::from("random"),
+                         String::from("random")];
+        names[player] = key.clone();
+
+        let mut seats = vec![Seat::Random, Seat::Random, Seat::Random, Seat::Random];
+        seats[player] = Seat::Remote {
+            key: key,
+            token: new_token(),
+        };
+
+        let id = new_token();
+        let m = Match {
+            state: State::new(&id, board, 4 * turns, &names),
+            seats: seats,
+            deadline: None,
+            finished_at: None,
+        };
+
+        let mut lobby = self.lobby.lock().unwrap();
+        lobby.purge();
+        lobby.games.insert(id.clone(), m);
+
+        self.wait_for_turn(lobby, &id, player)
+    }
+
+    fn arena(&self, key: String) -> Reply {
+        let board = self.load_map(None)?;
+
+        let mut lobby = self.lobby.lock().unwrap();
+        let ticket = lobby.next_ticket;
+        lobby.next_ticket += 1;
+        lobby.queue.push((ticket, key));
+
+        if lobby.queue.len() >= 4 {
+            let mut players: Vec<(u64, String)> = lobby.queue.drain(..4).collect();
+            rand::thread_rng().shuffle(&mut players);
+
+            let names = [players[0].1.clone(),
+                         players[1].1.clone(),
+                         players[2].1.clone(),
+                         players[3].1.clone()];
+
+            let id = new_token();
+            let mut state = State::new(&id, board, 4 * DEFAULT_TURNS, &names);
+            for h in state.game.heroes.iter_mut() {
+                h.elo = 1200;
+            }
+            state.hero = state.game.heroes[0].clone();
+
+            let mut seats = Vec::with_capacity(4);
+            for (i, (t, key)) in players.into_iter().enumerate() {
+                lobby.seated.insert(t, (id.clone(), i));
+                seats.push(Seat::Remote {
+                    key: key,
+                    token: new_token(),
+                });
+            }
+
+            lobby.purge();
+            lobby.games.insert(id,
+                               Match {
+                                   state: state,
+                                   seats: seats,
+                                   deadline: None,
+                                   finished_at: None,
+                               });
+            self.changed.notify_all();
+        }
+
+        loop {
+            if let Some((id, idx)) = lobby.seated.remove(&ticket) {
+                return self.wait_for_turn(lobby, &id, idx);
+            }
+
+            lobby = self.changed.wait(lobby).unwrap();
+        }
+    }
+
+    fn play(&self, id: &str, token: &str, key: &str, dir: Option<&String>) -> Reply {
+        let dir = parse_dir(dir)?;
+
+        let mut lobby = self.lobby.lock().unwrap();
+        let idx = {
+            let m = match lobby.games.get_mut(id) {
+                Some(m) => m,
+                None => return Err(not_found("Unknown game")),
+            };
+
+            let idx = m.seats
+                .iter()
+                .position(|s| match *s {
+                    Seat::Remote { token: ref t, .. } => t == token,
+                    Seat::Random => false,
+                });
+
+            let idx = match idx {
+                Some(i) => i,
+                None => return Err(not_found("Unknown token")),
+            };
+
+            if let Seat::Remote { key: ref k, .. } = m.seats[idx] {
+                if k != key {
+                    return Err(bad_request("Wrong key"));
+                }
+            }
+
+            self.advance(m);
+
+            if m.state.game.finished {
+                return Err(bad_request("The game is finished"));
+            }
+
+            if m.state.game.heroes[idx].crashed {
+                return Err(bad_request("Time out! Your hero has crashed"));
+            }
+
+            if m.state.game.turn % 4 != idx {
+                return Err(bad_request("Not your turn"));
+            }
+
+            m.state.make_move(dir);
+            m.deadline = None;
+            idx
+        };
+
+        self.changed.notify_all();
+        self.wait_for_turn(lobby, id, idx)
+    }
+
+    /// Plays the random movers and crashed heroes, and crashes remote players
+    /// who let their move timeout expire. Returns whether any move was made.
+    fn advance(&self, m: &mut Match) -> bool {
+        let mut moved = false;
+
+        while !m.state.game.finished {
+            let idx = m.state.game.turn % 4;
+
+            let dir = match m.seats[idx] {
+                Seat::Random => Random.choose_move(&mut m.state),
+                Seat::Remote { .. } if m.state.game.heroes[idx].crashed => Direction::Stay,
+                Seat::Remote { .. } => {
+                    match m.deadline {
+                        Some(d) if Instant::now() >= d => {
+                            m.deadline = None;
+                            m.state.game.heroes[idx].crashed = true;
+                            Direction::Stay
+                        }
+                        Some(_) => return moved,
+                        None => {
+                            if self.config.move_timeout > 0 {
+                                m.deadline = Some(Instant::now() +
+                                                  Duration::from_millis(self.config.move_timeout));
+                            }
+                            return moved;
+                        }
+                    }
+                }
+            };
+
+            m.state.make_move(dir);
+            moved = true;
+        }
+
+        moved
+    }
+
+    /// Blocks until it is hero `idx`'s turn in game `id` or the game is over,
+    /// then returns the state as that hero sees it.
+    fn wait_for_turn(&self, mut lobby: MutexGuard<Lobby>, id: &str, idx: usize) -> Reply {
+        loop {
+            {
+                let m = match lobby.games.get_mut(id) {
+                    Some(m) => m,
+                    None => return Err(not_found("Unknown game")),
+                };
+
+                if self.advance(m) {
+                    self.changed.notify_all();
+                }
+
+                if m.state.game.finished {
+                    if m.finished_at.is_none() {
+                        m.finished_at = Some(Instant::now());
+                    }
+                    return Ok(self.view(m, idx));
+                }
+
+                if m.state.game.heroes[idx].crashed {
+                    return Err(bad_request("Time out! Your hero has crashed"));
+                }
+
+                if m.state.game.turn % 4 == idx {
+                    return Ok(self.view(m, idx));
+                }
+            }
+
+            // Wake up regularly even without news, so that timeouts of
+            // players that went away get enforced.
+            let (guard, _) = self.changed
+                .wait_timeout(lobby, Duration::from_millis(50))
+                .unwrap();
+            lobby = guard;
+        }
+    }
+
+    fn view(&self, m: &Match, idx: usize) -> String {
+        let mut s = m.state.clone();
+        s.hero = s.game.heroes[idx].clone();
+
+        if let Seat::Remote { ref token, .. } = m.seats[idx] {
+            s.token = token.clone();
+        }
+
+        s.view_url = format!("http://{}/{}", self.config.address, s.game.id);
+        s.play_url = format!("http://{}/api/{}/{}/play",
+                             self.config.address,
+                             s.game.id,
+                             s.token);
+
+        serde_json::to_string(&s).unwrap()
+    }
+}
@@ -4,12 +4,13 @@ use std::hash::{Hash, Hasher};
 use fnv::FnvHasher;
 
 use game::Game;
+use board::Board;
 use hero::Hero;
 use tile::Tile;
 use direction::Direction;
 use position::Position;
 
-#[derive(Clone, Deserialize, Debug, Eq)]
+#[derive(Clone, Serialize, Deserialize, Debug, Eq)]
 pub struct State {
     pub game: Game,
     pub hero: Hero,
@@ -22,7 +23,6 @@ pub struct State {
 
 #[derive(Debug)]
 pub struct UnmakeInfo {
-    kill_depth: u8,
     heroes: [Hero; 4],
     tiles: Vec<(Position, Tile)>,
 }
@@ -49,58 +49,90 @@ impl PartialEq for State {
 }
 
 impl State {
-    fn kill(&mut self, hero_id: usize, killer_id: usize, umi: &mut UnmakeInfo) {
-        // println!("{} killed by {}", hero_id, killer_id);
-        umi.kill_depth += 1;
-        if umi.kill_depth > 4 {
-            println!("kill_depth exceeded 4!, hero_id: {}, killer_id: {}, umi: {:?}",
-                     hero_id,
-                     killer_id,
-                     umi);
-            return;
+    /// Sets up a fresh game on `board`, with the heroes placed where the
+    /// board has them. The state is seen from the perspective of hero 1.
+    pub fn new(id: &str, board: Board, max_turns: usize, names: &[String; 4]) -> State {
+        let spawns = board.hero_positions();
+        let heroes = [Hero::new(1, &names[0], spawns[0]),
+                      Hero::new(2, &names[1], spawns[1]),
+                      Hero::new(3, &names[2], spawns[2]),
+                      Hero::new(4, &names[3], spawns[3])];
+
+        State {
+            hero: heroes[0].clone(),
+            game: Game {
+                id: String::from(id),
+                turn: 0,
+                max_turns: max_turns,
+                heroes: heroes,
+                board: board,
+                finished: false,
+            },
+            token: String::default(),
+            view_url: String::default(),
+            play_url: String::default(),
         }
+    }
 
-        if killer_id > 0 {
-            self.game.heroes[killer_id - 1].mine_count += self.game.heroes[hero_id - 1].mine_count;
+    /// Hands all mines of `from` to `to` (0 makes them neutral).
+    fn transfer_mines(&mut self, from: usize, to: usize, umi: &mut UnmakeInfo) {
+        for i in 0..self.game.board.mine_pos.len() {
+            let pos = self.game.board.mine_pos[i];
+            if self.game.board.tile_at(&pos) == Tile::Mine(from) {
+                umi.tiles.push((pos, Tile::Mine(from)));
+                self.game.board.put_tile(&pos, Tile::Mine(to));
+            }
         }
-        self.game.heroes[hero_id - 1].mine_count = 0;
 
-        let mut mpos = vec![];
-        for pos in &self.game.board.mine_pos {
-            match self.game.board.tile_at(pos) {
-                Tile::Mine(hid) if hid == hero_id => mpos.push(pos.clone()),
-                _ => (),
-            }
+        if to > 0 {
+            self.game.heroes[to - 1].mine_count += self.game.heroes[from - 1].mine_count;
         }
+        self.game.heroes[from - 1].mine_count = 0;
+    }
 
-        // println!("{:?}", mpos);
+    /// Kills hero `hero_id`, handing its mines to `killer_id` (0 when it died
+    /// attacking a mine), and respawns it with full life. A hero standing on
+    /// the spawn point gets telefragged: it dies in turn, and its mines go to
+    /// the hero that respawned on top of it.
+    fn kill(&mut self, hero_id: usize, killer_id: usize, umi: &mut UnmakeInfo) {
+        let mut victim = hero_id;
+        let mut killer = killer_id;
+
+        // Spawn points are distinct, so every hero dies at most once here
+        // and the chain ends after at most four deaths.
+        loop {
+            self.transfer_mines(victim, killer, umi);
+
+            let old_pos = self.game.heroes[victim - 1].pos;
+            let spawn_pos = self.game.heroes[victim - 1].spawn_pos;
+
+            // A telefragged hero's tile has already been taken over by the
+            // hero that respawned there.
+            if self.game.board.tile_at(&old_pos) == Tile::Hero(victim) {
+                umi.tiles.push((old_pos, Tile::Hero(victim)));
+                self.game.board.put_tile(&old_pos, Tile::Air);
+            }
 
-        for ref pos in mpos {
-            umi.tiles.push((*pos, self.game.board.tile_at(pos)));
-            self.game.board.put_tile(pos, Tile::Mine(killer_id));
-        }
+            self.game.heroes[victim - 1].pos = spawn_pos;
+            self.game.heroes[victim - 1].life = 100;
 
-        let old_pos = &self.game.heroes[hero_id - 1].pos.clone();
-        let new_pos = &self.game.heroes[hero_id - 1].spawn_pos.clone();
-        self.game.heroes[hero_id - 1].pos = *new_pos;
-        self.game.heroes[hero_id - 1].life = 100;
+            umi.tiles.push((spawn_pos, self.game.board.tile_at(&spawn_pos)));
+            self.game.board.put_tile(&spawn_pos, Tile::Hero(victim));
 
-        umi.tiles.push((*old_pos, Tile::Hero(hero_id)));
-        self.game.board.put_tile(old_pos, Tile::Air);
+            let fragged = self.game
+                .heroes
+                .iter()
+                .find(|h| h.id != victim && h.pos == spawn_pos)
+                .map(|h| h.id);
 
-        for i in 1..4 {
-            if self.game.heroes[((hero_id - 1) + i) % 4].pos ==
-               self.game.heroes[hero_id - 1].spawn_pos {
-                let killed_id = self.game.heroes[((hero_id - 1) + i) % 4].id;
-                {
-                    self.kill(killed_id, hero_id, umi)
+            match fragged {
+                Some(id) => {
+                    killer = victim;
+                    victim = id;
                 }
+                None => break,
             }
         }
-
-        umi.tiles
-            .push((*new_pos, self.game.board.tile_at(new_pos)));
-        self.game.board.put_tile(new_pos, Tile::Hero(hero_id));
     }
 
     pub fn get_moves(&self) -> Vec<Direction> {
@@ -140,9 +172,12 @@ impl State {
         result
     }
 
+    /// Plays `direction` for the hero whose turn it is: the hero moves (or
+    /// drinks, or attacks a mine), fights all adjacent enemies, collects one
+    /// gold per mine it owns and finally loses one life to thirst, which
+    /// can't kill it. A hero that dies on a mine doesn't fight.
     pub fn make_move(&mut self, direction: Direction) -> UnmakeInfo {
         let mut result = UnmakeInfo {
-            kill_depth: 0u8,
             heroes: [self.game.heroes[0].clone(),
                      self.game.heroes[1].clone(),
                      self.game.heroes[2].clone(),
@@ -151,11 +186,11 @@ impl State {
         };
 
         let h_idx = (self.game.turn % 4) as usize;
+        let hero_id = h_idx + 1;
+        let target = self.game.heroes[h_idx].pos.neighbor(direction);
         let mut hero_died = false;
 
-        match self.game
-                  .board
-                  .tile_at(&self.game.heroes[h_idx].pos.neighbor(direction)) {
+        match self.game.board.tile_at(&target) {
             Tile::Wall | Tile::Hero(_) => (),
             Tile::Tavern => {
                 if self.game.heroes[h_idx].gold >= 2 {
@@ -167,63 +202,54 @@ impl State {
                 }
             }
             Tile::Air => {
-                result
-                    .tiles
-                    .push((self.game.heroes[h_idx].pos, Tile::Hero(h_idx + 1)));
-                self.game
-                    .board
-                    .put_tile(&self.game.heroes[h_idx].pos, Tile::Air);
-                result
-                    .tiles
-                    .push((self.game.heroes[h_idx].pos.neighbor(direction), Tile::Air));
-                self.game
-                    .board
-                    .put_tile(&self.game.heroes[h_idx].pos.neighbor(direction),
-                              Tile::Hero(h_idx + 1));
-                self.game.heroes[h_idx].pos = self.game.heroes[h_idx].pos.neighbor(direction);
-            }
-            Tile::Mine(hero_id) => {
-                if hero_id != h_idx + 1 {
-                    if self.game.heroes[h_idx].life <= 20 {
-                        hero_died = true;
-                        self.kill(h_idx + 1, 0, &mut result)
-                    } else {
-                        if hero_id > 0 {
-                            self.game.heroes[hero_id - 1].mine_count -= 1;
-                        }
-                        self.game.heroes[h_idx].mine_count += 1;
-                        self.game.heroes[h_idx].life -= 20;
-                        result
-                            .tiles
-                            .push((self.game.heroes[h_idx].pos.neighbor(direction),
-                                   self.game
-                                       .board
-                                       .tile_at(&self.game.heroes[h_idx]
-                                                     .pos
-                                                     .neighbor(direction))));
-                        self.game
-                            .board
-                            .put_tile(&self.game.heroes[h_idx].pos.neighbor(direction),
-                                      Tile::Mine(h_idx + 1));
+                let pos = self.game.heroes[h_idx].pos;
+                result.tiles.push((pos, Tile::Hero(hero_id)));
+                self.game.board.put_tile(&pos, Tile::Air);
+                result.tiles.push((target, Tile::Air));
+                self.game.board.put_tile(&target, Tile::Hero(hero_id));
+                self.game.heroes[h_idx].pos = target;
+            }
+            Tile::Mine(owner) if owner == hero_id => (),
+            Tile::Mine(owner) => {
+                // The mine stays with its owner if the attacker dies.
+                if self.game.heroes[h_idx].life <= 20 {
+                    hero_died = true;
+                    self.kill(hero_id, 0, &mut result);
+                } else {
+                    if owner > 0 {
+                        self.game.heroes[owner - 1].mine_count -= 1;
                     }
+                    self.game.heroes[h_idx].mine_count += 1;
+                    self.game.heroes[h_idx].life -= 20;
+                    result.tiles.push((target, Tile::Mine(owner)));
+                    self.game.board.put_tile(&target, Tile::Mine(hero_id));
                 }
-            }
+            }
         }
 
         if !hero_died {
-            for i in 0..(4 as usize) {
-                if i == h_idx {
+            // The enemies to attack are fixed before the first blow, so a
+            // hero that gets telefragged next to us isn't hit on arrival.
+            // A kill can telefrag us in turn, which ends the fight.
+            let pos = self.game.heroes[h_idx].pos;
+            let mut adjacent = [false; 4];
+            for i in 0..4 {
+                adjacent[i] = i != h_idx &&
+                              self.game.heroes[i].pos.manhattan_distance(&pos) == 1;
+            }
+
+            for i in 0..4 {
+                if !adjacent[i] || self.game.heroes[i].pos.manhattan_distance(&pos) != 1 {
                     continue;
                 }
 
-                if self.game.heroes[i]
-                       .pos
-                       .manhattan_distance(&self.game.heroes[h_idx].pos) == 1 {
-                    if self.game.heroes[i].life <= 20 {
-                        self.kill(i + 1, h_idx + 1, &mut result);
-                    } else {
-                        self.game.heroes[i].life -= 20;
+                if self.game.heroes[i].life <= 20 {
+                    self.kill(i + 1, hero_id, &mut result);
+                    if self.game.heroes[h_idx].pos != pos {
+                        break;
                     }
+                } else {
+                    self.game.heroes[i].life -= 20;
                 }
             }
         }
@@ -0,0 +1,107 @@
+use std::fmt;
+
+use state::State;
+
+pub struct GameSummary {
+    pub gold: u16,
+    pub rank: usize,
+    pub mines: u8,
+    pub elo_delta: f64,
+    pub view_url: String,
+}
+
+impl GameSummary {
+    pub fn from_state(s: &State) -> GameSummary {
+        let me = &s.game.heroes[s.hero.id - 1];
+
+        let rank = 1 + s.game.heroes.iter().filter(|h| h.gold > me.gold).count();
+
+        // Same K-factor and expectation as the rank adjustment in Bot::eval.
+        let mut elo_delta = 0.0;
+        for enemy in &s.game.heroes {
+            if enemy.id == me.id {
+                continue;
+            }
+
+            let q_self = f64::powf(10.0, me.rating() as f64 / 400.0);
+            let q_enemy = f64::powf(10.0, enemy.rating() as f64 / 400.0);
+            let expected_self = q_self / (q_self + q_enemy);
+
+            let score = if me.gold > enemy.gold {
+                1.0
+            } else if me.gold == enemy.gold {
+                0.5
+            } else {
+                0.0
+            };
+
+            elo_delta += 16.0 * (score - expected_self);
+        }
+
+        GameSummary {
+            gold: me.gold,
+            rank: rank,
+            mines: me.mine_count,
+            elo_delta: elo_delta,
+            view_url: s.view_url.clone(),
+        }
+    }
+}
+
+impl fmt::Display for GameSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,
+               "rank {}, gold {}, mines {}, Elo {:+.1}, {}",
+               self.rank,
+               self.gold,
+               self.mines,
+               self.elo_delta,
+               self.view_url)
+    }
+}
+
+#[derive(Default)]
+pub struct Totals {
+    pub games: usize,
+    pub crashed: usize,
+    pub failed: usize,
+    pub wins: usize,
+    pub gold: u64,
+    pub rank_sum: usize,
+    pub elo_delta: f64,
+}
+
+impl Totals {
+    pub fn add(&mut self, g: &GameSummary) {
+        self.games += 1;
+        self.gold += g.gold as u64;
+        self.rank_sum += g.rank;
+        self.elo_delta += g.elo_delta;
+
+        if g.rank == 1 {
+            self.wins += 1;
+        }
+    }
+}
+
+impl fmt::Display for Totals {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.games == 0 {
+            return write!(f,
+                          "no games finished, {} crashed, {} failed",
+                          self.crashed,
+                          self.failed);
+        }
+
+        write!(f,
+               "{} games ({} crashed, {} failed), {} wins, avg rank {:.2}, avg gold {:.1}, \
+                Elo {:+.1}",
+               self.games,
+               self.crashed,
+               self.failed,
+               self.wins,
+               self.rank_sum as f64 / self.games as f64,
+               self.gold as f64 / self.games as f64,
+               self.elo_delta)
+    }
+}
@@ -0,0 +1,151 @@
+use std::collections::VecDeque;
+
+use state::State;
+
+/// Time the server needs to process a move, on top of the network.
+const MARGIN_MS: i64 = 50;
+/// Assumed round trip until the first move has been measured.
+const DEFAULT_LATENCY_MS: i64 = 100;
+const LATENCY_SAMPLES: usize = 8;
+
+/// Decides how long to think about each move, given the server's move
+/// timeout and the round trips measured so far.
+pub struct TimeManager {
+    move_timeout: i64,
+    round_trips: VecDeque<i64>,
+}
+
+impl TimeManager {
+    pub fn new(move_timeout: i64) -> TimeManager {
+        TimeManager {
+            move_timeout: move_timeout,
+            round_trips: VecDeque::with_capacity(LATENCY_SAMPLES),
+        }
+    }
+
+    /// Records how long a move took from sending it to receiving the next
+    /// state.
+    pub fn record_round_trip(&mut self, milliseconds: i64) {
+        if self.round_trips.len() == LATENCY_SAMPLES {
+            self.round_trips.pop_front();
+        }
+        self.round_trips.push_back(milliseconds);
+    }
+
+    /// Round trips also contain the time the other heroes took, so the
+    /// fastest recent one is the best guess for the network alone.
+    pub fn latency(&self) -> i64 {
+        self.round_trips.iter().cloned().min().unwrap_or(DEFAULT_LATENCY_MS)
+    }
+
+    /// Milliseconds to spend on the move in `s`: most of what the timeout
+    /// leaves after the network, and all of it on critical turns.
+    pub fn budget(&self, s: &mut State) -> i64 {
+        let available = (self.move_timeout - self.latency() - MARGIN_MS).max(MARGIN_MS);
+
+        if is_critical(s) {
+            available
+        } else {
+            available * 3 / 4
+        }
+    }
+}
+
+/// A turn is critical when a fight is near and its outcome isn't obvious,
+/// or when we and an enemy are both close to the same mine.
+pub fn is_critical(s: &mut State) -> bool {
+    let hero = s.hero.clone();
+
+    for e in &s.game.heroes {
+        if e.id == hero.id || e.crashed {
+            continue;
+        }
+
+        let d = s.game.board.shortest_path_length(&hero.pos, &e.pos);
+        if d <= 4 && (hero.life <= 50 || (e.life as i32 - hero.life as i32).abs() <= 20) {
+            return true;
+        }
+    }
+
+    for mp in &s.game.board.mine_pos.clone() {
+        if s.game.board.shortest_path_length(&hero.pos, mp) > 3 {
+            continue;
+        }
+
+        for e in &s.game.heroes {
+            if e.id != hero.id && !e.crashed &&
+               s.game.board.shortest_path_length(&e.pos, mp) <= 3 {
+                return true;
+            }
+        }
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use direction::Direction;
+    use mapgen;
+    use state::State;
+    use super::{is_critical, TimeManager, LATENCY_SAMPLES, MARGIN_MS};
+
+    /// The start of a game, where the heroes are far apart.
+    fn quiet() -> State {
+        let names = [String::from("a"), String::from("b"), String::from("c"), String::from("d")];
+        let board = mapgen::generate(mapgen::size_for_seed(10), 10);
+        let mut s = State::new("test", board, 400, &names);
+        assert!(!is_critical(&mut s));
+        s
+    }
+
+    /// Hero 1 walks towards hero 2 until a fight is near.
+    fn critical() -> State {
+        let mut s = quiet();
+        while !is_critical(&mut s) {
+            let (from, to) = (s.game.heroes[0].pos, s.game.heroes[1].pos);
+            let dir = s.game.board.direction_to(&from, &to);
+            s.make_move(dir);
+            for _ in 1..4 {
+                s.make_move(Direction::Stay);
+            }
+        }
+        s
+    }
+
+    #[test]
+    fn budget_keeps_a_quarter_back_unless_the_turn_is_critical() {
+        let timer = TimeManager::new(1000);
+
+        // 1000 - 100 assumed latency - 50 margin.
+        assert_eq!(timer.budget(&mut critical()), 850);
+        assert_eq!(timer.budget(&mut quiet()), 850 * 3 / 4);
+    }
+
+    #[test]
+    fn budget_subtracts_the_fastest_recent_round_trip() {
+        let mut timer = TimeManager::new(1000);
+        timer.record_round_trip(300);
+        timer.record_round_trip(120);
+        timer.record_round_trip(200);
+        assert_eq!(timer.latency(), 120);
+
+        assert_eq!(timer.budget(&mut critical()), 830);
+        assert_eq!(timer.budget(&mut quiet()), 830 * 3 / 4);
+
+        // The fast round trip drops out once enough newer ones came in.
+        for _ in 0..LATENCY_SAMPLES {
+            timer.record_round_trip(200);
+        }
+        assert_eq!(timer.budget(&mut critical()), 750);
+    }
+
+    #[test]
+    fn budget_never_drops_below_the_margin() {
+        let mut timer = TimeManager::new(100);
+        timer.record_round_trip(500);
+
+        assert_eq!(timer.budget(&mut critical()), MARGIN_MS);
+        assert_eq!(timer.budget(&mut quiet()), MARGIN_MS * 3 / 4);
+    }
+}
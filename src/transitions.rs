@@ -0,0 +1,237 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+
+use serde_json;
+
+use direction::Direction;
+use state::State;
+
+/// A state, the moves played from it and the state the server answered
+/// with. The client records one per server response; hand-written cases
+/// describing single rules use the same format.
+#[derive(Serialize, Deserialize)]
+pub struct Transition {
+    #[serde(default)]
+    pub name: String,
+    pub before: State,
+    pub moves: Vec<Direction>,
+    pub after: State,
+}
+
+/// Lists what differs between two states, as far as the rules are concerned.
+pub fn differences(a: &State, b: &State) -> Vec<String> {
+    let mut result = Vec::new();
+
+    if a.game.turn != b.game.turn {
+        result.push(format!("turn: {} vs. {}", a.game.turn, b.game.turn));
+    }
+
+    for (h, e) in a.game.heroes.iter().zip(b.game.heroes.iter()) {
+        if h.pos != e.pos {
+            result.push(format!("hero {} pos: ({}, {}) vs. ({}, {})",
+                                h.id,
+                                h.pos.x,
+                                h.pos.y,
+                                e.pos.x,
+                                e.pos.y));
+        }
+        if h.life != e.life {
+            result.push(format!("hero {} life: {} vs. {}", h.id, h.life, e.life));
+        }
+        if h.gold != e.gold {
+            result.push(format!("hero {} gold: {} vs. {}", h.id, h.gold, e.gold));
+        }
+        if h.mine_count != e.mine_count {
+            result.push(format!("hero {} mines: {} vs. {}", h.id, h.mine_count, e.mine_count));
+        }
+        if h.spawn_pos != e.spawn_pos {
+            result.push(format!("hero {} spawn: ({}, {}) vs. ({}, {})",
+                                h.id,
+                                h.spawn_pos.x,
+                                h.spawn_pos.y,
+                                e.spawn_pos.x,
+                                e.spawn_pos.y));
+        }
+        if h.crashed != e.crashed {
+            result.push(format!("hero {} crashed: {} vs. {}", h.id, h.crashed, e.crashed));
+        }
+        if h.last_dir != e.last_dir {
+            result.push(format!("hero {} last move: {:?} vs. {:?}", h.id, h.last_dir, e.last_dir));
+        }
+    }
+
+    if a.game.board.tiles() != b.game.board.tiles() {
+        result.push(format!("board:\n{}vs.\n{}", a.game.board, b.game.board));
+    }
+
+    result
+}
+
+/// Plays the moves of `t` with State::make_move and compares the outcome with
+/// the recorded state.
+pub fn check(t: &Transition) -> Vec<String> {
+    let mut s = t.before.clone();
+    s.game.board.initialize();
+
+    for dir in &t.moves {
+        s.make_move(*dir);
+    }
+
+    let mut after = t.after.clone();
+    after.game.board.initialize();
+
+    differences(&s, &after)
+}
+
+/// Checks every transition in the given JSON lines files and returns whether
+/// all of them matched.
+pub fn verify(paths: &[String]) -> bool {
+    let mut passed = 0;
+    let mut failed = 0;
+
+    for path in paths {
+        let f = match File::open(path) {
+            Ok(f) => f,
+            Err(e) => {
+                println!("{}: {}", path, e);
+                failed += 1;
+                continue;
+            }
+        };
+
+        for (i, line) in BufReader::new(f).lines().enumerate() {
+            let line = match line {
+                Ok(l) => l,
+                Err(e) => {
+                    println!("{}:{}: {}", path, i + 1, e);
+                    failed += 1;
+                    break;
+                }
+            };
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let t: Transition = match serde_json::from_str(&line) {
+                Ok(t) => t,
+                Err(e) => {
+                    println!("{}:{}: {}", path, i + 1, e);
+                    failed += 1;
+                    continue;
+                }
+            };
+
+            let diffs = check(&t);
+            if diffs.is_empty() {
+                passed += 1;
+            } else {
+                failed += 1;
+                println!("{}:{}: {} FAILED (simulated vs. recorded)",
+                         path,
+                         i + 1,
+                         t.name);
+                for d in diffs {
+                    println!("    {}", d);
+                }
+            }
+        }
+    }
+
+    println!("{} passed, {} failed", passed, failed);
+    failed == 0
+}
+
+/// Appends transitions to a JSON lines file.
+pub struct Recorder {
+    file: File,
+}
+
+impl Recorder {
+    pub fn open(path: &str) -> Result<Recorder, String> {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map(|f| Recorder { file: f })
+            .map_err(|e| format!("Could not open {}: {}", path, e))
+    }
+
+    pub fn record(&mut self, before: &State, moves: &[Direction], after: &State) {
+        let t = Transition {
+            name: format!("{} turn {}", before.game.id, before.game.turn),
+            before: before.clone(),
+            moves: moves.to_vec(),
+            after: after.clone(),
+        };
+
+        if let Err(e) = writeln!(self.file, "{}", serde_json::to_string(&t).unwrap()) {
+            println!("Could not record transition: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::io::{BufRead, BufReader};
+
+    use serde_json;
+
+    use position::Position;
+    use super::{check, differences, Transition};
+
+    fn load(name: &str) -> Vec<Transition> {
+        let path = format!("{}/transitions/{}", env!("CARGO_MANIFEST_DIR"), name);
+        let f = File::open(&path).unwrap_or_else(|e| panic!("{}: {}", path, e));
+
+        BufReader::new(f)
+            .lines()
+            .map(|line| line.unwrap())
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| serde_json::from_str(&line).unwrap_or_else(|e| panic!("{}: {}", path, e)))
+            .collect()
+    }
+
+    fn check_all(name: &str) {
+        let transitions = load(name);
+        assert!(!transitions.is_empty(), "{} has no transitions", name);
+
+        let failures: Vec<String> = transitions.iter()
+            .filter_map(|t| {
+                let diffs = check(t);
+                if diffs.is_empty() {
+                    None
+                } else {
+                    Some(format!("{}:\n    {}", t.name, diffs.join("\n    ")))
+                }
+            })
+            .collect();
+
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+
+    #[test]
+    fn rules() {
+        check_all("rules.jsonl");
+    }
+
+    #[test]
+    fn recorded() {
+        check_all("recorded.jsonl");
+    }
+
+    #[test]
+    fn differences_include_spawn_points_crashes_and_last_moves() {
+        let t = load("rules.jsonl").remove(0);
+        let mut a = t.after.clone();
+        a.game.board.initialize();
+        assert!(differences(&a, &a).is_empty());
+
+        let mut b = a.clone();
+        b.game.heroes[0].spawn_pos = Position { x: 0, y: 0 };
+        b.game.heroes[1].crashed = true;
+        b.game.heroes[2].last_dir = String::from("North");
+        assert_eq!(differences(&a, &b).len(), 3);
+    }
+}
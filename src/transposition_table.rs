@@ -1,6 +1,8 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use direction::Direction;
 use mv::Move;
 
-#[repr(packed)]
 #[derive(Default, Clone)]
 pub struct Entry {
     pub mv: Move,
@@ -11,10 +13,91 @@ pub struct Entry {
     pub age: u16,
 }
 
+impl Entry {
+    fn bounds(&self) -> u64 {
+        (self.lower as u32 as u64) << 32 | self.upper as u32 as u64
+    }
+
+    fn data(&self) -> u64 {
+        let mut mv = self.mv.player as u64;
+        for d in &self.mv.directions {
+            mv = mv << 3 | *d as u8 as u64;
+        }
+
+        mv << 32 | (self.depth as u64) << 16 | self.age as u64
+    }
+
+    fn unpack(hash: u64, bounds: u64, data: u64) -> Entry {
+        let mv = data >> 32;
+        let mut directions = [Direction::Stay; 4];
+        for (i, d) in directions.iter_mut().enumerate() {
+            *d = Direction::from((mv >> (9 - 3 * i) & 7) as u8);
+        }
+
+        Entry {
+            mv: Move {
+                player: (mv >> 12) as u8,
+                directions: directions,
+            },
+            hash: hash,
+            lower: (bounds >> 32) as u32 as i32,
+            upper: bounds as u32 as i32,
+            depth: (data >> 16) as u16,
+            age: data as u16,
+        }
+    }
+}
+
+/// An entry as stored in the table. `check` is the hash xor the other two
+/// words, so an entry torn by two threads writing at once doesn't match any
+/// position and reads as a miss.
+#[derive(Default)]
+struct Slot {
+    check: AtomicU64,
+    bounds: AtomicU64,
+    data: AtomicU64,
+}
+
+impl Slot {
+    fn load(&self, hash: u64) -> Option<Entry> {
+        let bounds = self.bounds.load(Ordering::Relaxed);
+        let data = self.data.load(Ordering::Relaxed);
+
+        if self.check.load(Ordering::Relaxed) ^ bounds ^ data != hash {
+            return None;
+        }
+
+        Some(Entry::unpack(hash, bounds, data))
+    }
+
+    fn store(&self, e: &Entry) {
+        let bounds = e.bounds();
+        let data = e.data();
+
+        self.check.store(e.hash ^ bounds ^ data, Ordering::Relaxed);
+        self.bounds.store(bounds, Ordering::Relaxed);
+        self.data.store(data, Ordering::Relaxed);
+    }
+
+    /// Depth and age of whatever is stored, torn or not.
+    fn depth_and_age(&self) -> (u16, u16) {
+        let data = self.data.load(Ordering::Relaxed);
+        ((data >> 16) as u16, data as u16)
+    }
+
+    fn clear(&self) {
+        self.check.store(0, Ordering::Relaxed);
+        self.bounds.store(0, Ordering::Relaxed);
+        self.data.store(0, Ordering::Relaxed);
+    }
+}
+
+/// A transposition table that any number of threads can probe and store to
+/// without locking.
 pub struct Table {
     num_entries: u64,
-    always: Vec<Entry>,
-    depthpref: Vec<Entry>,
+    always: Vec<Slot>,
+    depthpref: Vec<Slot>,
 }
 
 impl Table {
@@ -23,48 +106,36 @@ impl Table {
             panic!("Num entries must be even, got {}.", num_entries);
         }
 
+        let half = (num_entries / 2) as usize;
+
         Table {
             num_entries: num_entries / 2,
-            always: vec![Entry::default(); (num_entries / 2) as usize],
-            depthpref: vec![Entry::default(); (num_entries / 2) as usize],
+            always: (0..half).map(|_| Slot::default()).collect(),
+            depthpref: (0..half).map(|_| Slot::default()).collect(),
         }
     }
 
-    pub fn probe(&self, hash: u64) -> Option<Entry> {
-        let idx = (hash % self.num_entries) as usize;
-
-        if self.depthpref[idx].hash == hash {
-            let ret = self.depthpref[idx].clone();
-            return Some(ret);
+    pub fn clear(&self) {
+        for s in self.always.iter().chain(self.depthpref.iter()) {
+            s.clear();
         }
+    }
 
-        if self.always[idx].hash == hash {
-            let ret = self.always[idx].clone();
-            return Some(ret);
-        }
+    pub fn probe(&self, hash: u64) -> Option<Entry> {
+        let idx = (hash % self.num_entries) as usize;
 
-        None
+        self.depthpref[idx].load(hash).or_else(|| self.always[idx].load(hash))
     }
 
-    pub fn store(&mut self, e: Entry) {
+    pub fn store(&self, e: Entry) {
         let idx = (e.hash % self.num_entries) as usize;
+        let (depth, age) = self.depthpref[idx].depth_and_age();
 
-        if self.depthpref[idx].depth <= e.depth || self.depthpref[idx].age + 15 < e.age {
-            self.depthpref[idx].mv = e.mv;
-            self.depthpref[idx].hash = e.hash;
-            self.depthpref[idx].lower = e.lower;
-            self.depthpref[idx].upper = e.upper;
-            self.depthpref[idx].depth = e.depth;
-            self.depthpref[idx].age = e.age;
-
+        if depth <= e.depth || age + 15 < e.age {
+            self.depthpref[idx].store(&e);
             return;
         }
 
-        self.always[idx].mv = e.mv;
-        self.always[idx].hash = e.hash;
-        self.always[idx].lower = e.lower;
-        self.always[idx].upper = e.upper;
-        self.always[idx].depth = e.depth;
-        self.always[idx].age = e.age;
+        self.always[idx].store(&e);
     }
 }
@@ -0,0 +1,237 @@
+use std::fs::File;
+use std::io::Write;
+
+use rand;
+use rand::Rng;
+use serde_json;
+use serde_json::Value;
+
+use bot::Bot;
+use config::TuneConfig;
+use mapgen;
+use policy::Policy;
+use runner;
+use state::State;
+use weights::Weights;
+
+/// The weights SPSA changes: name, the step that counts as one unit, and
+/// the range the weight is kept in.
+const PARAMS: [(&'static str, f64, f64, f64); 10] = [("life_per_gold", 5.0, 1.0, 100.0),
+                                                     ("enemy_range", 1.0, 0.0, 20.0),
+                                                     ("enemy_standoff", 1.0, 0.0, 20.0),
+                                                     ("life_bucket", 5.0, 1.0, 100.0),
+                                                     ("near_bonus", 0.5, -10.0, 10.0),
+                                                     ("near_penalty", 0.5, -10.0, 10.0),
+                                                     ("thirst_margin", 5.0, 0.0, 100.0),
+                                                     ("tavern_delay", 1.0, 0.0, 20.0),
+                                                     ("rank_weight", 2000.0, 0.0, 100000.0),
+                                                     ("elo_k", 4.0, 0.0, 64.0)];
+
+/// Step size of the parameter updates, and how far apart the two weights
+/// compared in an iteration are, both in steps of PARAMS. They
+/// shrink over the iterations as recommended by Spall.
+const SPSA_A: f64 = 0.5;
+const SPSA_C: f64 = 1.0;
+const SPSA_ALPHA: f64 = 0.602;
+const SPSA_GAMMA: f64 = 0.101;
+
+/// Plays games between two teams of two Beagles each, on generated maps.
+struct Arena {
+    turns: usize,
+    move_time: i64,
+    seed: u64,
+}
+
+impl Arena {
+    /// Plays `n` games of `a` against `b` and returns by how many ranks `a`
+    /// finished ahead of `b` on average, along with the average gold of
+    /// both. Every map is played twice, the second time with the teams in
+    /// each other's seats, so neither gets the luckier spawn points; `n` has
+    /// to be even.
+    fn play(&mut self, a: &Weights, b: &Weights, n: usize) -> (f64, f64, f64) {
+        let mut rank_diff = 0.0;
+        let mut gold = (0.0, 0.0);
+
+        for i in 0..n {
+            // Seats of team a: 0 and 2 in the first game on a map, 1 and 3
+            // in the second.
+            let a_first = i % 2 == 0;
+            let mut players: Vec<Box<dyn Policy>> = Vec::with_capacity(4);
+            let mut names = [String::new(), String::new(), String::new(), String::new()];
+
+            for seat in 0..4 {
+                let on_a = (seat % 2 == 0) == a_first;
+                let mut bot = Bot::with_table_size(200000);
+                bot.set_verbose(false);
+                bot.set_move_time(self.move_time);
+                bot.set_weights(if on_a { a.clone() } else { b.clone() });
+                players.push(Box::new(bot));
+
+                // Heroes with the same name play as a team.
+                names[seat] = String::from(if on_a { "a" } else { "b" });
+            }
+
+            let seed = self.seed;
+            if !a_first {
+                self.seed = self.seed.wrapping_add(1);
+            }
+
+            let board = mapgen::generate(mapgen::size_for_seed(seed), seed);
+            let state = State::new(&format!("tune-{}", seed), board, 4 * self.turns, &names);
+            let state = runner::play_game(state, &mut players);
+            let ranks = runner::ranks(&state);
+
+            for (seat, h) in state.game.heroes.iter().enumerate() {
+                if (seat % 2 == 0) == a_first {
+                    rank_diff -= ranks[seat] as f64 / 2.0;
+                    gold.0 += h.gold as f64 / 2.0;
+                } else {
+                    rank_diff += ranks[seat] as f64 / 2.0;
+                    gold.1 += h.gold as f64 / 2.0;
+                }
+            }
+        }
+
+        let n = n as f64;
+        (rank_diff / n, gold.0 / n, gold.1 / n)
+    }
+}
+
+/// The weights at `theta`, which counts in steps of PARAMS away from
+/// `start`. Integer weights are rounded.
+fn weights_at(start: &Weights, theta: &[f64]) -> Weights {
+    let mut value = serde_json::to_value(start).unwrap();
+
+    for (&(name, scale, min, max), t) in PARAMS.iter().zip(theta.iter()) {
+        let old = value[name].clone();
+        let x = (old.as_f64().unwrap() + t * scale).max(min).min(max);
+
+        value[name] = if old.is_u64() {
+            Value::from(x.round() as u64)
+        } else {
+            Value::from(x)
+        };
+    }
+
+    serde_json::from_value(value).unwrap()
+}
+
+fn show(v: &Value) -> String {
+    match v.as_u64() {
+        Some(n) => n.to_string(),
+        None => format!("{:.3}", v.as_f64().unwrap_or(0.0)),
+    }
+}
+
+/// Tunes the evaluation weights with SPSA (simultaneous perturbation
+/// stochastic approximation): every iteration moves all weights a random
+/// step up or down at once, plays the two resulting sets of weights against
+/// each other, and moves towards the one that did better. Finally, the tuned
+/// weights play the starting ones to see whether they are any better.
+pub fn run(config: &TuneConfig) -> Result<(), String> {
+    let start = match config.weights {
+        Some(ref path) => Weights::load(path)?,
+        None => Weights::default(),
+    };
+
+    let mut rng = rand::thread_rng();
+    let mut arena = Arena {
+        turns: config.turns,
+        move_time: config.move_time,
+        seed: config.seed.unwrap_or_else(|| rng.gen()),
+    };
+    let mut theta = vec![0f64; PARAMS.len()];
+
+    for k in 0..config.iterations {
+        let a_k = SPSA_A / (k as f64 + 1.0 + config.iterations as f64 / 10.0).powf(SPSA_ALPHA);
+        let c_k = SPSA_C / (k as f64 + 1.0).powf(SPSA_GAMMA);
+
+        let delta: Vec<f64> = PARAMS.iter()
+            .map(|_| if rng.gen() { 1.0 } else { -1.0 })
+            .collect();
+        let plus: Vec<f64> = theta.iter().zip(delta.iter()).map(|(t, d)| t + c_k * d).collect();
+        let minus: Vec<f64> = theta.iter().zip(delta.iter()).map(|(t, d)| t - c_k * d).collect();
+
+        let (diff, _, _) = arena.play(&weights_at(&start, &plus),
+                                      &weights_at(&start, &minus),
+                                      config.games);
+
+        for (t, d) in theta.iter_mut().zip(delta.iter()) {
+            *t += a_k * diff / (2.0 * c_k * d);
+        }
+
+        println!("Iteration {}/{}: plus ahead by {:+.2} ranks",
+                 k + 1,
+                 config.iterations,
+                 diff);
+    }
+
+    let tuned = weights_at(&start, &theta);
+
+    println!("\nweight               start      tuned");
+    let before = serde_json::to_value(&start).unwrap();
+    let after = serde_json::to_value(&tuned).unwrap();
+    for &(name, _, _, _) in PARAMS.iter() {
+        println!("{:<16}  {:>8}  {:>9}", name, show(&before[name]), show(&after[name]));
+    }
+
+    let text = serde_json::to_string_pretty(&tuned).unwrap();
+    File::create(&config.out)
+        .and_then(|mut f| writeln!(f, "{}", text))
+        .map_err(|e| format!("Could not write {}: {}", config.out, e))?;
+    println!("\nWrote the tuned weights to {}", config.out);
+
+    if config.verify_games > 0 {
+        let (diff, tuned_gold, start_gold) = arena.play(&tuned, &start, config.verify_games);
+        println!("Tuned against start over {} games: {:+.2} ranks, average gold {:.1} against \
+                  {:.1}",
+                 config.verify_games,
+                 diff,
+                 tuned_gold,
+                 start_gold);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{weights_at, PARAMS};
+    use weights::Weights;
+
+    fn theta(name: &str, t: f64) -> Vec<f64> {
+        PARAMS.iter().map(|p| if p.0 == name { t } else { 0.0 }).collect()
+    }
+
+    #[test]
+    fn no_steps_keep_the_weights() {
+        let start = Weights::default();
+        assert_eq!(weights_at(&start, &vec![0.0; PARAMS.len()]), start);
+    }
+
+    #[test]
+    fn integer_weights_are_rounded() {
+        let start = Weights::default();
+
+        // enemy_range moves by 1.0 per step, life_bucket by 5.0.
+        assert_eq!(weights_at(&start, &theta("enemy_range", 0.6)).enemy_range, 7);
+        assert_eq!(weights_at(&start, &theta("enemy_range", -0.4)).enemy_range, 6);
+        assert_eq!(weights_at(&start, &theta("life_bucket", 0.5)).life_bucket, 23);
+    }
+
+    #[test]
+    fn float_weights_are_not_rounded() {
+        let w = weights_at(&Weights::default(), &theta("near_bonus", 0.3));
+        assert!((w.near_bonus - 1.15).abs() < 1e-9);
+    }
+
+    #[test]
+    fn weights_stay_in_their_range() {
+        let start = Weights::default();
+
+        assert_eq!(weights_at(&start, &theta("life_per_gold", -100.0)).life_per_gold, 1.0);
+        assert_eq!(weights_at(&start, &theta("rank_weight", 1000.0)).rank_weight, 100000.0);
+        assert_eq!(weights_at(&start, &theta("life_bucket", -10.0)).life_bucket, 1);
+        assert_eq!(weights_at(&start, &theta("enemy_range", 100.0)).enemy_range, 20);
+    }
+}
@@ -0,0 +1,99 @@
+use std::io;
+use std::io::{BufRead, Write};
+
+use replay;
+use replay::Turn;
+
+const HELP: &'static str = "Enter or n: next turn, p: previous turn, N or g N: jump to game \
+                            turn N, f/l: first/last turn, q: quit";
+
+fn render(turns: &[Turn], idx: usize) -> String {
+    let t = &turns[idx];
+    let s = &t.state;
+    let mut out = String::new();
+
+    out.push_str(&format!("Turn {}/{} (step {}/{}){}\n\n",
+                          s.game.turn,
+                          s.game.max_turns,
+                          idx + 1,
+                          turns.len(),
+                          if s.game.finished { ", finished" } else { "" }));
+    out.push_str(&format!("{}\n", s.game.board));
+
+    for h in &s.game.heroes {
+        out.push_str(&format!("{} @{} {:<16} life {:>3}  gold {:>5}  mines {:>2}{}\n",
+                              if h.id == s.hero.id { '*' } else { ' ' },
+                              h.id,
+                              h.name,
+                              h.life,
+                              h.gold,
+                              h.mine_count,
+                              if h.crashed { "  crashed" } else { "" }));
+    }
+
+    if let Some(mv) = t.mv {
+        out.push_str(&format!("\nMove: {}", mv));
+        if let Some(ref info) = t.search {
+            out.push_str(&format!(" (depth {}, score {}, {} nodes, {} ms)",
+                                  info.depth,
+                                  info.score,
+                                  info.nodes,
+                                  info.time_ms));
+            if !info.pv.is_empty() {
+                out.push_str(&format!("\nPV: {}", info.pv.join(", ")));
+            }
+        }
+        out.push('\n');
+    }
+
+    out
+}
+
+/// Returns the last step at or before game turn `turn`.
+fn find_turn(turns: &[Turn], turn: usize) -> usize {
+    match turns.iter().rposition(|t| t.state.game.turn <= turn) {
+        Some(i) => i,
+        None => 0,
+    }
+}
+
+pub fn run(path: &str, start_turn: Option<usize>) -> Result<(), String> {
+    let turns = replay::load(path)?;
+    if turns.is_empty() {
+        return Err(format!("{} contains no turns", path));
+    }
+
+    let mut idx = start_turn.map_or(0, |t| find_turn(&turns, t));
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+
+    loop {
+        // Clear the terminal and draw the current turn.
+        print!("\x1b[2J\x1b[H{}\n{}\n> ", render(&turns, idx), HELP);
+        io::stdout().flush().ok();
+
+        let line = match lines.next() {
+            Some(Ok(l)) => l,
+            _ => return Ok(()),
+        };
+
+        let mut words = line.split_whitespace();
+        match words.next() {
+            None | Some("n") => idx = (idx + 1).min(turns.len() - 1),
+            Some("p") => idx = idx.saturating_sub(1),
+            Some("f") => idx = 0,
+            Some("l") => idx = turns.len() - 1,
+            Some("q") => return Ok(()),
+            Some("g") => {
+                if let Some(Ok(t)) = words.next().map(|w| w.parse::<usize>()) {
+                    idx = find_turn(&turns, t);
+                }
+            }
+            Some(w) => {
+                if let Ok(t) = w.parse::<usize>() {
+                    idx = find_turn(&turns, t);
+                }
+            }
+        }
+    }
+}
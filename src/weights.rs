@@ -0,0 +1,108 @@
+use std::fs::File;
+use std::io::Read;
+
+use serde_json;
+
+/// The coefficients of the evaluation. The defaults are the values Beagle
+/// was tuned by hand with; a weights file only needs to list the ones it
+/// changes.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Weights {
+    /// Points of life worth one gold.
+    pub life_per_gold: f64,
+    /// Enemies closer than this (in steps) count for or against us.
+    pub enemy_range: u8,
+    /// At exactly this distance, an enemy counts against us unless we are
+    /// two life buckets stronger.
+    pub enemy_standoff: u8,
+    /// Life is compared in buckets of this size. A hit takes 20 life.
+    pub life_bucket: u8,
+    /// For every enemy in range with no more life buckets than us.
+    pub near_bonus: f64,
+    /// For every enemy in range with more life buckets than us, or at the
+    /// standoff distance.
+    pub near_penalty: f64,
+    /// Our hero needs a drink before taking a mine if it would be left with
+    /// this much life or less when it gets there.
+    pub thirst_margin: u8,
+    /// Turns spent at the tavern on the way to the next mine.
+    pub tavern_delay: u8,
+    /// Weight of the expected rating change against predicted gold.
+    pub rank_weight: f64,
+    /// K factor of the rating change, per opponent.
+    pub elo_k: f64,
+}
+
+impl Default for Weights {
+    fn default() -> Weights {
+        Weights {
+            life_per_gold: 20.0,
+            enemy_range: 6,
+            enemy_standoff: 3,
+            life_bucket: 20,
+            near_bonus: 1.0,
+            near_penalty: 1.0,
+            thirst_margin: 20,
+            tavern_delay: 2,
+            rank_weight: 10000.0,
+            elo_k: 16.0,
+        }
+    }
+}
+
+impl Weights {
+    /// Reads weights from a JSON file.
+    pub fn load(path: &str) -> Result<Weights, String> {
+        let mut text = String::new();
+        File::open(path)
+            .and_then(|mut f| f.read_to_string(&mut text))
+            .map_err(|e| format!("Could not read {}: {}", path, e))?;
+
+        Weights::parse(&text).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    /// Reads weights from JSON text.
+    pub fn parse(text: &str) -> Result<Weights, String> {
+        let weights: Weights = serde_json::from_str(text).map_err(|e| e.to_string())?;
+
+        if weights.life_bucket == 0 {
+            return Err(String::from("life_bucket must be at least 1"));
+        }
+        if weights.life_per_gold <= 0.0 {
+            return Err(String::from("life_per_gold must be positive"));
+        }
+
+        Ok(weights)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Weights;
+
+    #[test]
+    fn missing_weights_keep_their_defaults() {
+        let w = Weights::parse(r#"{"near_bonus": 2.5, "enemy_range": 4}"#).unwrap();
+        assert_eq!(w,
+                   Weights {
+                       near_bonus: 2.5,
+                       enemy_range: 4,
+                       ..Weights::default()
+                   });
+        assert_eq!(Weights::parse("{}").unwrap(), Weights::default());
+    }
+
+    #[test]
+    fn unknown_weights_are_rejected() {
+        let e = Weights::parse(r#"{"near_bonus": 2.5, "nearbonus": 1.0}"#).unwrap_err();
+        assert!(e.contains("unknown field `nearbonus`"), "{}", e);
+    }
+
+    #[test]
+    fn weights_that_break_the_evaluation_are_rejected() {
+        assert!(Weights::parse(r#"{"life_bucket": 0}"#).is_err());
+        assert!(Weights::parse(r#"{"life_per_gold": 0.0}"#).is_err());
+        assert!(Weights::parse(r#"{"enemy_range": -1}"#).is_err());
+    }
+}
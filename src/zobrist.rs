@@ -1,20 +1,30 @@
 use rand;
-use rand::Rng;
+use rand::{Rng, SeedableRng, StdRng};
 
 pub struct ZobristTable {
     pub keys: [u64; 12 * 35 * 35],
 }
 
-impl Default for ZobristTable {
-    fn default() -> ZobristTable {
+impl ZobristTable {
+    /// The same keys for the same seed, so that hash collisions and with them
+    /// node counts are the same on every run.
+    pub fn seeded(seed: u64) -> ZobristTable {
+        let seed = [seed as usize, (seed >> 32) as usize];
+        let mut rng: StdRng = SeedableRng::from_seed(&seed[..]);
         let mut result = ZobristTable { keys: [0; 12 * 35 * 35] };
 
         for i in 0..(12 * 35 * 35) {
-            result.keys[i] = rand::thread_rng().gen();
+            result.keys[i] = rng.gen();
         }
 
         result
     }
 }
 
+impl Default for ZobristTable {
+    fn default() -> ZobristTable {
+        ZobristTable::seeded(rand::thread_rng().gen())
+    }
+}
+
 pub static mut ZOBRIST: ZobristTable = ZobristTable { keys: [0; 12 * 35 * 35] };